@@ -6,6 +6,13 @@ export default class PersonRoute extends Route {
 
   async model(params) {
     await this.genea.populate();
-    return this.genea.person(params.id);
+
+    // Send links from before a renumbering on to the person's current page
+    let id = this.genea.currentId(params.id);
+    if (id !== params.id) {
+      this.replaceWith('person', id);
+    }
+
+    return this.genea.person(id);
   }
 }
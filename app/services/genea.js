@@ -4,6 +4,7 @@ export default class GeneaService extends Service {
     #roots = null;
     #people = {};
     #partnerships = {};
+    #redirects = {};
 
     isPopulated() {
         return this.#roots !== null;
@@ -29,6 +30,12 @@ export default class GeneaService extends Service {
                         throw new Error(`unexpected type of object ${object.type}`);
                 }
             }
+
+            // Maps ids people had before being renumbered to their current ids
+            let redirectsFetch = await fetch('/redirects.json');
+            if (redirectsFetch.ok) {
+                this.#redirects = await redirectsFetch.json();
+            }
         }
     }
 
@@ -71,7 +78,15 @@ export default class GeneaService extends Service {
         if (!this.isPopulated()) {
             throw new Error("genea not populated");
         }
-        return this.getPersonById(id);
+        return this.populatedPersonById(this.currentId(id));
+    }
+
+    /// The id that the person who had `id` in an earlier version of the tree has now.
+    currentId(id) {
+        if (id in this.#people || !(id in this.#redirects)) {
+            return id;
+        }
+        return this.#redirects[id];
     }
 }
 
@@ -124,6 +139,10 @@ export class Person {
         return this.#attributes.gender;
     }
 
+    get henryNumber() {
+        return this.#attributes.henryNumber;
+    }
+
     get isSpouse() {
         return this.#attributes.isSpouse;
     }
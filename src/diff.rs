@@ -0,0 +1,204 @@
+//! Differences between two versions of a family tree, described in terms of people
+//! rather than the lines of `genea.doc`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use serde::Serialize;
+
+use crate::genea::{match_people, Genea, Person};
+
+#[cfg(test)]
+mod tests;
+
+/// A change to one person between two versions of the tree. People are identified by
+/// their id in the newer version (or, if they were removed, in the older one).
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Change {
+    Added {
+        id: String,
+        name: String,
+    },
+
+    Removed {
+        id: String,
+        name: String,
+    },
+
+    Renamed {
+        id: String,
+        #[serde(rename = "oldName")]
+        old_name: String,
+        #[serde(rename = "newName")]
+        new_name: String,
+    },
+
+    Reparented {
+        id: String,
+        name: String,
+        #[serde(rename = "oldParents")]
+        old_parents: Vec<String>,
+        #[serde(rename = "newParents")]
+        new_parents: Vec<String>,
+    },
+
+    Renumbered {
+        id: String,
+        name: String,
+        #[serde(rename = "oldHenryNumber")]
+        old_henry_number: Option<String>,
+        #[serde(rename = "newHenryNumber")]
+        new_henry_number: Option<String>,
+    },
+
+    CommentsChanged {
+        id: String,
+        name: String,
+        #[serde(rename = "oldComments")]
+        old_comments: String,
+        #[serde(rename = "newComments")]
+        new_comments: String,
+    },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added { id, name } => write!(f, "added {name} ({id})"),
+            Change::Removed { id, name } => write!(f, "removed {name} ({id})"),
+            Change::Renamed {
+                id,
+                old_name,
+                new_name,
+            } => write!(f, "renamed {old_name} to {new_name} ({id})"),
+            Change::Reparented {
+                id,
+                name,
+                old_parents,
+                new_parents,
+            } => write!(
+                f,
+                "moved {name} ({id}) from {} to {}",
+                parents_string(old_parents),
+                parents_string(new_parents),
+            ),
+            Change::Renumbered {
+                id,
+                name,
+                old_henry_number,
+                new_henry_number,
+            } => write!(
+                f,
+                "renumbered {name} ({id}) from {} to {}",
+                old_henry_number.as_deref().unwrap_or("none"),
+                new_henry_number.as_deref().unwrap_or("none"),
+            ),
+            Change::CommentsChanged {
+                id,
+                name,
+                old_comments,
+                new_comments,
+            } => write!(
+                f,
+                "changed comments of {name} ({id}) from {old_comments:?} to {new_comments:?}"
+            ),
+        }
+    }
+}
+
+fn parents_string(parents: &[String]) -> String {
+    if parents.is_empty() {
+        "no parents".to_string()
+    } else {
+        parents.join(" + ")
+    }
+}
+
+/// The changes that turn `old` into `new`: first the people who were removed,
+/// then the changes to everybody else in the order they appear in `new`.
+pub fn diff(old: &Genea, new: &Genea) -> Vec<Change> {
+    let matches = match_people(old, new);
+
+    let mut changes: Vec<Change> = old
+        .people()
+        .filter(|p| !matches.contains_key(p))
+        .map(|p| Change::Removed {
+            id: old.id(p),
+            name: old[p].name.clone(),
+        })
+        .collect();
+
+    let old_by_new: BTreeMap<Person, Person> = matches.iter().map(|(&o, &n)| (n, o)).collect();
+
+    for new_person in new.people() {
+        let new_data = &new[new_person];
+        let id = new.id(new_person);
+        let name = new_data.name.clone();
+
+        let Some(&old_person) = old_by_new.get(&new_person) else {
+            changes.push(Change::Added { id, name });
+            continue;
+        };
+        let old_data = &old[old_person];
+
+        if old_data.name != new_data.name {
+            changes.push(Change::Renamed {
+                id: id.clone(),
+                old_name: old_data.name.clone(),
+                new_name: new_data.name.clone(),
+            });
+        }
+
+        // Parents are compared as people, so a parent who was renamed or
+        // renumbered does not count as a change here.
+        let old_parents = parents(old, old_person);
+        let new_parents = parents(new, new_person);
+        let old_parents_now: BTreeSet<Option<Person>> = old_parents
+            .iter()
+            .map(|p| matches.get(p).copied())
+            .collect();
+        let new_parents_now: BTreeSet<Option<Person>> =
+            new_parents.iter().map(|&p| Some(p)).collect();
+        if old_parents_now != new_parents_now {
+            changes.push(Change::Reparented {
+                id: id.clone(),
+                name: name.clone(),
+                old_parents: old_parents.iter().map(|&p| old[p].name.clone()).collect(),
+                new_parents: new_parents.iter().map(|&p| new[p].name.clone()).collect(),
+            });
+        }
+
+        let old_henry_number = old_data.henry_number().map(|hn| hn.to_string());
+        let new_henry_number = new_data.henry_number().map(|hn| hn.to_string());
+        if old_henry_number != new_henry_number {
+            changes.push(Change::Renumbered {
+                id: id.clone(),
+                name: name.clone(),
+                old_henry_number,
+                new_henry_number,
+            });
+        }
+
+        if old_data.comments != new_data.comments {
+            changes.push(Change::CommentsChanged {
+                id,
+                name,
+                old_comments: old_data.comments.clone(),
+                new_comments: new_data.comments.clone(),
+            });
+        }
+    }
+
+    changes
+}
+
+fn parents(genea: &Genea, person: Person) -> Vec<Person> {
+    genea[person]
+        .child_in
+        .iter()
+        .flat_map(|&p| genea[p].parents.iter().copied())
+        .collect()
+}
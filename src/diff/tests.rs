@@ -0,0 +1,95 @@
+//! The changes reported between two versions of a small tree.
+
+use serde_json::json;
+
+use super::diff;
+use crate::genea::{Genea, ParseOptions};
+
+fn parse(text: &str) -> Genea {
+    Genea::from_genea_text("genea.doc", text, ParseOptions::default()).unwrap()
+}
+
+/// The changes from `old` to `new`, as they are printed.
+fn changes(old: &str, new: &str) -> Vec<String> {
+    diff(&parse(old), &parse(new))
+        .iter()
+        .map(|c| c.to_string())
+        .collect()
+}
+
+#[test]
+fn unchanged_tree_has_no_changes() {
+    let text = " 1 0 M 1 0 0 Skon\n 1 1 M 0 0 0 Sarek\n";
+    assert!(changes(text, text).is_empty());
+}
+
+#[test]
+fn older_sibling_is_added_and_the_rest_renumbered() {
+    assert_eq!(
+        changes(
+            " 1 0 M 1 0 0 Skon\n 1 1 M 0 0 0 Sarek\n",
+            " 1 0 M 2 0 0 Skon\n 1 1 M 0 0 0 Solkar\n 1 2 M 0 0 0 Sarek\n",
+        ),
+        [
+            "added Solkar (1-1)",
+            "renumbered Sarek (1-2) from 1-1 to 1-2",
+        ]
+    );
+}
+
+#[test]
+fn removed_people_come_first() {
+    assert_eq!(
+        changes(
+            " 1 0 M 2 0 0 Skon\n 1 1 M 0 0 0 Solkar\n 1 2 M 0 0 0 Sarek\n",
+            " 1 0 M 1 0 0 Skon\n 1 1 M 0 0 0 Sarek\n",
+        ),
+        [
+            "removed Solkar (1-1)",
+            "renumbered Sarek (1-1) from 1-2 to 1-1",
+        ]
+    );
+}
+
+#[test]
+fn renamed_person_keeps_their_place() {
+    assert_eq!(
+        changes(
+            " 1 0 M 1 0 0 Skon\n 1 1 M 0 0 0 Sarek\n",
+            " 1 0 M 1 0 0 Skon\n 1 1 M 0 0 0 Sarek of Vulcan\n",
+        ),
+        ["renamed Sarek to Sarek of Vulcan (1-1)"]
+    );
+}
+
+#[test]
+fn child_moved_to_another_parent_is_reparented() {
+    assert_eq!(
+        changes(
+            " 1 0 M 2 0 0 Skon\n 1 1 M 1 0 0 Sarek\n 1 1 1 M 0 0 0 Spock\n 1 2 M 0 0 0 Sybok\n",
+            " 1 0 M 2 0 0 Skon\n 1 1 M 0 0 0 Sarek\n 1 2 M 1 0 0 Sybok\n 1 2 1 M 0 0 0 Spock\n",
+        ),
+        [
+            "moved Spock (1-2-1) from Sarek to Sybok",
+            "renumbered Spock (1-2-1) from 1-1-1 to 1-2-1",
+        ]
+    );
+}
+
+#[test]
+fn changes_serialize_with_their_kind() {
+    let changes = diff(
+        &parse(" 1 0 M 0 0 0 Skon\\Scientist\n"),
+        &parse(" 1 0 M 0 0 0 Skon\\Ambassador\n"),
+    );
+    assert_eq!(
+        serde_json::to_value(&changes).unwrap(),
+        json!([{
+            "kind": "commentsChanged",
+            "id": "1",
+            "name": "Skon",
+            "oldComments": "Scientist",
+            "newComments": "Ambassador",
+        }])
+    );
+}
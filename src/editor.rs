@@ -0,0 +1,391 @@
+//! An interactive session for editing `genea.doc` without touching its columns: the
+//! maintainer moves around the tree and adds people and comments with simple commands,
+//! and the file is regenerated (see [`Genea::to_genea_doc`]) after every change.
+
+use std::{
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::genea::{read_genea_doc_for_editing, Gender, Genea, ParseOptions, Person};
+
+#[cfg(test)]
+mod tests;
+
+const HELP: &str = "\
+commands:
+  go <henry number, id or name>     select somebody (e.g. `go 1-1-2`, `go Spock`)
+  up                                select the parent of the selected person
+  show                              show the selected person and their families
+  child [<spouse>] <M|F|?> <name>   add a child with the spouse with that spousal index
+                                    (which can be left out if there is only one)
+  spouse <M|F|?> <name>             add a spouse
+  move <position>                   move the selected person among their siblings
+  comment [<text>]                  replace (or, without text, remove) the comments
+  private [<text>]                  replace (or, without text, remove) the private comments
+  delete                            remove the selected person and everybody listed under them
+  undo                              undo the last change
+  help                              show this message
+  quit                              stop editing
+";
+
+/// Runs an editing session for the `genea.doc` file at `path`, reading commands from
+/// stdin. Each change is checked by regenerating the file, and written out if it is valid;
+/// so that only the edited lines change, files that are not laid out the way they are
+/// regenerated are refused (see [`read_genea_doc_for_editing`]).
+pub fn run(path: &Path) -> anyhow::Result<()> {
+    let mut session = Session::open(path)?;
+    println!("type `help` for a list of commands");
+    session.run(std::io::stdin().lock())
+}
+
+struct Session {
+    path: PathBuf,
+    genea: Genea,
+
+    /// The text of the file before each change, ending with its current text
+    history: Vec<String>,
+
+    /// The selected person
+    current: Option<Person>,
+}
+
+impl Session {
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let (text, genea) = read_genea_doc_for_editing(path)?;
+        Ok(Session {
+            path: path.to_path_buf(),
+            genea,
+            history: vec![text],
+            current: None,
+        })
+    }
+
+    /// Carries out the commands read from `input`, one per line, until it ends or there
+    /// is a `quit` command. Commands that fail are reported and the session goes on.
+    fn run(&mut self, input: impl BufRead) -> anyhow::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            print!("{}> ", self.prompt());
+            std::io::stdout().flush()?;
+
+            let Some(line) = lines.next() else {
+                println!();
+                break;
+            };
+
+            let line = line?;
+            let (command, argument) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+            let argument = argument.trim();
+            let result = match command {
+                "" => Ok(()),
+                "go" => self.go(argument),
+                "up" => self.up(),
+                "show" => self.current().map(|person| self.show(person)),
+                "child" => self.add_child(argument),
+                "spouse" => self.add_spouse(argument),
+                "move" => self.move_child(argument),
+                "comment" => self.change(|session| {
+                    let person = session.current()?;
+                    session.genea.set_comments(person, argument)?;
+                    Ok(format!(
+                        "changed the comments on {}",
+                        session.genea[person].name
+                    ))
+                }),
+                "private" => self.change(|session| {
+                    let person = session.current()?;
+                    session.genea.set_private_comments(person, argument)?;
+                    Ok(format!(
+                        "changed the private comments on {}",
+                        session.genea[person].name
+                    ))
+                }),
+                "delete" => self.delete(),
+                "undo" => self.undo(),
+                "help" => {
+                    print!("{HELP}");
+                    Ok(())
+                }
+                "quit" | "exit" => break,
+                _ => Err(anyhow::anyhow!(
+                    "unknown command `{command}`; type `help` for a list of commands"
+                )),
+            };
+
+            if let Err(e) = result {
+                println!("error: {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn prompt(&self) -> String {
+        match self.current {
+            Some(person) => describe(&self.genea, person),
+            None => String::new(),
+        }
+    }
+
+    fn current(&self) -> anyhow::Result<Person> {
+        self.current
+            .ok_or_else(|| anyhow::anyhow!("nobody is selected; use `go` to select somebody"))
+    }
+
+    /// Selects the person with the henry number or id `target`, or else the one person
+    /// whose name contains `target`.
+    fn go(&mut self, target: &str) -> anyhow::Result<()> {
+        let genea = &self.genea;
+        if target.is_empty() {
+            anyhow::bail!("say who to go to, e.g. `go 1-1-2` or `go Spock`");
+        }
+
+        let person = match genea.descendant(target) {
+            Ok(person) => person,
+            Err(_) => match genea.people().find(|&p| genea.id(p) == target) {
+                Some(person) => person,
+                None => self.find_by_name(target)?,
+            },
+        };
+
+        self.current = Some(person);
+        self.show(person);
+        Ok(())
+    }
+
+    fn find_by_name(&self, name: &str) -> anyhow::Result<Person> {
+        let genea = &self.genea;
+        let exact: Vec<Person> = genea
+            .people()
+            .filter(|&p| genea[p].is_named(name))
+            .collect();
+        let matches: Vec<Person> = if exact.is_empty() {
+            let name = name.to_lowercase();
+            genea
+                .people()
+                .filter(|&p| {
+                    std::iter::once(&genea[p].name)
+                        .chain(&genea[p].aliases)
+                        .any(|n| n.to_lowercase().contains(&name))
+                })
+                .collect()
+        } else {
+            exact
+        };
+
+        match matches[..] {
+            [] => anyhow::bail!("nobody is called `{name}`"),
+            [person] => Ok(person),
+            _ => {
+                for &person in &matches {
+                    println!("  {:<12} {}", genea.id(person), genea[person].name);
+                }
+                anyhow::bail!("several people match `{name}`; go to one of them by id")
+            }
+        }
+    }
+
+    fn up(&mut self) -> anyhow::Result<()> {
+        let person = self.current()?;
+        let Some(partnership) = self.genea[person].child_in else {
+            anyhow::bail!("{} has no parents in the tree", self.genea[person].name);
+        };
+
+        let parent = self.genea[partnership].primary_parent;
+        self.current = Some(parent);
+        self.show(parent);
+        Ok(())
+    }
+
+    fn show(&self, person: Person) {
+        let genea = &self.genea;
+        let person_data = &genea[person];
+        println!("{}, {}", describe(genea, person), person_data.gender);
+        if !person_data.aliases.is_empty() {
+            println!("  also called {}", person_data.aliases.join(", "));
+        }
+        if !person_data.comments.is_empty() {
+            println!("  comments: {}", person_data.comments);
+        }
+        if !person_data.private_comments.is_empty() {
+            println!("  private comments: {}", person_data.private_comments);
+        }
+
+        if let Some(partnership) = person_data.child_in {
+            let parents: Vec<String> = genea[partnership]
+                .parents
+                .iter()
+                .map(|&p| describe(genea, p))
+                .collect();
+            println!("  child of {}", parents.join(" and "));
+        }
+
+        let mut partnerships = person_data.parent_in.clone();
+        partnerships.sort_by_key(|&p| genea[p].spousal_index);
+        for partnership in partnerships {
+            let partnership_data = &genea[partnership];
+            match (
+                partnership_data.other_parent(person),
+                partnership_data.spousal_index,
+            ) {
+                (Some(partner), Some(spousal_index))
+                    if partnership_data.primary_parent == person =>
+                {
+                    println!("  spouse {spousal_index}: {}", describe(genea, partner))
+                }
+                (Some(partner), _) => println!("  partner of {}", describe(genea, partner)),
+                (None, _) => println!("  children without a spouse:"),
+            }
+
+            for &child in &partnership_data.children {
+                println!("    {}", describe(genea, child));
+            }
+        }
+    }
+
+    /// Adds a child of the selected person, given as `[<spouse>] <gender> <name>`.
+    fn add_child(&mut self, argument: &str) -> anyhow::Result<()> {
+        let (spousal_index, argument) = match argument.split_once(' ') {
+            Some((first, rest)) if first.chars().all(|c| c.is_ascii_digit()) => {
+                (Some(first.parse()?), rest.trim())
+            }
+            _ => (None, argument),
+        };
+        let (gender, name) = gender_and_name(argument)?;
+
+        self.change(|session| {
+            let parent = session.current()?;
+            let genea = &mut session.genea;
+            let partnership = genea.partnership_for_children(parent, spousal_index)?;
+            let position = genea.numbered_children(parent).len() + 1;
+            let child = genea.add_child(partnership, position, name, gender)?;
+            Ok(format!("added {}", describe(genea, child)))
+        })
+    }
+
+    /// Adds a spouse of the selected person, given as `<gender> <name>`.
+    fn add_spouse(&mut self, argument: &str) -> anyhow::Result<()> {
+        let (gender, name) = gender_and_name(argument)?;
+
+        self.change(|session| {
+            let person = session.current()?;
+            let genea = &mut session.genea;
+            let (spouse, _) = genea.add_spouse(person, name, gender)?;
+            Ok(format!("added {}", describe(genea, spouse)))
+        })
+    }
+
+    fn move_child(&mut self, argument: &str) -> anyhow::Result<()> {
+        let position: usize = argument
+            .parse()
+            .map_err(|_| anyhow::anyhow!("say which position to move to, e.g. `move 1`"))?;
+
+        self.change(|session| {
+            let child = session.current()?;
+            session.genea.move_child(child, position)?;
+            Ok(format!("moved {}", describe(&session.genea, child)))
+        })
+    }
+
+    fn delete(&mut self) -> anyhow::Result<()> {
+        self.change(|session| {
+            let person = session.current()?;
+            let genea = &mut session.genea;
+            let name = genea[person].name.clone();
+
+            // Afterwards, select whoever they were listed under.
+            let listed_under = match genea[person].child_in {
+                Some(partnership) => Some(genea[partnership].primary_parent),
+                None => genea[person]
+                    .parent_in
+                    .iter()
+                    .map(|&p| genea[p].primary_parent)
+                    .find(|&p| p != person),
+            };
+
+            let before = genea.people().count();
+            let renumber = genea.delete_subtree(person)?;
+            let removed = before - genea.people().count();
+            session.current = listed_under.and_then(renumber);
+
+            Ok(match removed {
+                1 => format!("removed {name}"),
+                _ => format!(
+                    "removed {name} and {} people listed under them",
+                    removed - 1
+                ),
+            })
+        })
+    }
+
+    fn undo(&mut self) -> anyhow::Result<()> {
+        if self.history.len() < 2 {
+            anyhow::bail!("there is nothing to undo");
+        }
+
+        let selected = self.selected_id();
+        self.history.pop();
+        std::fs::write(&self.path, self.history.last().unwrap())?;
+        self.reload(selected)?;
+        println!("undid the last change");
+        Ok(())
+    }
+
+    /// Makes a change to the tree with `edit`, which returns a description of what it did.
+    /// If that works and the tree can still be written out as a valid `genea.doc` file,
+    /// the file is written; otherwise, the tree is put back the way it was.
+    fn change(
+        &mut self,
+        edit: impl FnOnce(&mut Session) -> anyhow::Result<String>,
+    ) -> anyhow::Result<()> {
+        // `edit` may renumber people, or fail halfway, so remember the selection first.
+        let selected = self.selected_id();
+        let result = edit(self).and_then(|message| Ok((message, self.genea.to_genea_doc()?)));
+        let (message, text) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                self.reload(selected)?;
+                return Err(e);
+            }
+        };
+
+        std::fs::write(&self.path, &text)?;
+        self.history.push(text);
+        println!("{message}");
+        Ok(())
+    }
+
+    /// The id of the selected person (see [`Genea::id`]), which, unlike the `Person`,
+    /// still identifies them once the file is parsed again.
+    fn selected_id(&self) -> Option<String> {
+        self.current.map(|p| self.genea.id(p))
+    }
+
+    /// Parses the current text of the file again, selecting the person with the id
+    /// `selected`, if there still is one.
+    fn reload(&mut self, selected: Option<String>) -> anyhow::Result<()> {
+        self.genea = Genea::from_genea_text(
+            self.path.display(),
+            self.history.last().unwrap(),
+            ParseOptions::default(),
+        )?;
+        self.current =
+            selected.and_then(|id| self.genea.people().find(|&p| self.genea.id(p) == id));
+        Ok(())
+    }
+}
+
+fn describe(genea: &Genea, person: Person) -> String {
+    format!("{} ({})", genea[person].name, genea.id(person))
+}
+
+/// Splits `<gender> <name>`.
+fn gender_and_name(argument: &str) -> anyhow::Result<(Gender, &str)> {
+    let Some((gender, name)) = argument.split_once(' ') else {
+        anyhow::bail!("give a gender (`M`, `F` or `?`) and a name, e.g. `M Sybok`");
+    };
+
+    Ok((Gender::from_str(gender)?, name.trim()))
+}
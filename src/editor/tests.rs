@@ -0,0 +1,152 @@
+//! Editing sessions driven by a script of commands, on a copy of a small `genea.doc`.
+
+use std::path::PathBuf;
+
+use super::Session;
+use crate::genea::{Genea, ParseOptions};
+
+/// Sarek's family, with counts that match the tree.
+const FAMILY: &str = "\
+ 1 0 M 3 2 0 Sarek
+ 1 0 F 2 0 1 Amanda
+ 1 1 M 0 0 0 Spock
+ 1 2 F 0 0 0 Michael
+ 1 0 F 1 0 2 T'Rea
+ 1 3 M 0 0 0 Sybok
+";
+
+/// A session on a copy of `FAMILY`, laid out the way it is regenerated, in a directory
+/// of its own that is removed when the session is dropped.
+struct TestSession(Session);
+
+impl TestSession {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("family-tree-editor-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("genea.doc");
+        let genea = Genea::from_genea_text("genea.doc", FAMILY, ParseOptions::default()).unwrap();
+        std::fs::write(&path, genea.to_genea_doc().unwrap()).unwrap();
+        TestSession(Session::open(&path).unwrap())
+    }
+
+    fn run(&mut self, commands: &str) {
+        self.0.run(commands.as_bytes()).unwrap();
+    }
+
+    fn selected(&self) -> Option<String> {
+        self.0.current.map(|p| self.0.genea[p].name.clone())
+    }
+
+    fn path(&self) -> &PathBuf {
+        &self.0.path
+    }
+
+    /// The tree in the file as it is now.
+    fn saved(&self) -> Genea {
+        Genea::from_genea_doc(self.path()).unwrap()
+    }
+}
+
+impl Drop for TestSession {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(self.path().parent().unwrap());
+    }
+}
+
+fn henry_number(genea: &Genea, name: &str) -> Option<String> {
+    let person = genea.people().find(|&p| genea[p].name == name)?;
+    Some(genea[person].henry_number.as_ref()?.to_string())
+}
+
+#[test]
+fn go_selects_by_henry_number_id_or_name() {
+    let mut session = TestSession::new("go");
+    session.run("go 1-3\n");
+    assert_eq!(session.selected().as_deref(), Some("Sybok"));
+    session.run("go 1--2\n");
+    assert_eq!(session.selected().as_deref(), Some("T'Rea"));
+    session.run("go michael\n");
+    assert_eq!(session.selected().as_deref(), Some("Michael"));
+    session.run("up\n");
+    assert_eq!(session.selected().as_deref(), Some("Sarek"));
+}
+
+#[test]
+fn go_to_nobody_keeps_the_selection() {
+    let mut session = TestSession::new("go-nobody");
+    session.run("go Spock\ngo 1-9\ngo Spook\ngo S\n");
+    assert_eq!(session.selected().as_deref(), Some("Spock"));
+}
+
+#[test]
+fn child_is_added_with_the_chosen_spouse_and_saved() {
+    let mut session = TestSession::new("child");
+    session.run("go Sarek\nchild 2 F Sybil\n");
+
+    let saved = session.saved();
+    assert_eq!(henry_number(&saved, "Sybil").as_deref(), Some("1-4"));
+    let sybil = saved.people().find(|&p| saved[p].name == "Sybil").unwrap();
+    let parents: Vec<&str> = saved[saved[sybil].child_in.unwrap()]
+        .parents
+        .iter()
+        .map(|&p| saved[p].name.as_str())
+        .collect();
+    assert_eq!(parents, ["Sarek", "T'Rea"]);
+}
+
+#[test]
+fn child_without_a_spouse_needs_one_when_there_are_several() {
+    let mut session = TestSession::new("child-spouse");
+    let text = std::fs::read_to_string(session.path()).unwrap();
+    session.run("go Sarek\nchild F Sybil\n");
+    assert_eq!(std::fs::read_to_string(session.path()).unwrap(), text);
+    assert_eq!(session.selected().as_deref(), Some("Sarek"));
+}
+
+#[test]
+fn undo_puts_back_the_file_as_it_was() {
+    let mut session = TestSession::new("undo");
+    let text = std::fs::read_to_string(session.path()).unwrap();
+
+    session.run("go Sybok\nmove 1\n");
+    assert_eq!(
+        henry_number(&session.saved(), "Sybok").as_deref(),
+        Some("1-1")
+    );
+    session.run("go Sarek\nchild 1 M Sybil\nundo\n");
+    assert_eq!(henry_number(&session.saved(), "Sybil"), None);
+    session.run("undo\n");
+    assert_eq!(std::fs::read_to_string(session.path()).unwrap(), text);
+
+    // There is nothing left to undo.
+    session.run("undo\n");
+    assert_eq!(std::fs::read_to_string(session.path()).unwrap(), text);
+    assert_eq!(session.selected().as_deref(), Some("Sarek"));
+}
+
+#[test]
+fn failed_change_keeps_the_tree_and_the_selection() {
+    let mut session = TestSession::new("failed");
+    session.run("go Spock\n");
+
+    // Moving Spock renumbers him, so he has to be found again by his id from before.
+    let result = session.0.change(|session| {
+        let spock = session.current()?;
+        session.genea.move_child(spock, 2)?;
+        anyhow::bail!("changed my mind")
+    });
+    assert!(result.is_err());
+    assert_eq!(session.selected().as_deref(), Some("Spock"));
+    assert_eq!(
+        henry_number(&session.0.genea, "Spock").as_deref(),
+        Some("1-1")
+    );
+}
+
+#[test]
+fn quit_ends_the_session() {
+    let mut session = TestSession::new("quit");
+    session.run("go Sarek\nquit\nchild 1 M Sybil\n");
+    assert_eq!(henry_number(&session.saved(), "Sybil"), None);
+}
@@ -1,24 +1,77 @@
-use std::{collections::BTreeSet, path::Path};
+use std::{collections::BTreeSet, io::Read, path::Path};
 
 use anyhow::Context;
+use itertools::Itertools;
 
+mod edit;
 mod error;
+mod fix;
+mod matching;
 mod parser;
+mod source_map;
+#[cfg(test)]
+mod testing;
+mod tokenizer;
+mod writer;
+
+pub use edit::{insert_child_into_genea_doc, move_child_in_genea_doc, read_genea_doc_for_editing};
+pub use error::{ParseError, ParseErrors};
+pub use fix::{assign_person_ids, fix_genea_doc, format_genea_doc};
+pub use matching::match_people;
+pub use parser::ParseOptions;
+pub use writer::write_genea_doc;
 
 /// The family tree. Indexable via `Person` and `Partnership` values.
 #[derive(Default)]
 pub struct Genea {
+    /// The text before the first person line
+    preamble: String,
+
+    /// The blank lines after the last person line, kept so that the file can be
+    /// written out again as it was
+    epilogue: String,
+
     maintainer_link: Option<String>,
     people: Vec<PersonData>,
     partnerships: Vec<PartnershipData>,
+
+    /// Problems found while parsing that did not stop us from building the tree
+    warnings: Vec<ParseError>,
 }
 
 impl Genea {
+    /// Parses the `genea.doc` file at `path`; a path of `-` reads from stdin.
     pub fn from_genea_doc(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::from_genea_doc_with_options(path, ParseOptions::default())
+    }
+
+    /// Like [`Genea::from_genea_doc`], but with control over how the file is parsed.
+    pub fn from_genea_doc_with_options(
+        path: impl AsRef<Path>,
+        options: ParseOptions,
+    ) -> anyhow::Result<Self> {
         let path = path.as_ref();
+        if path == Path::new("-") {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .context("reading stdin")?;
+            return Self::from_genea_text("<stdin>", &text, options);
+        }
+
         let text = std::fs::read_to_string(path)
             .with_context(|| format!("reading `{}`", path.display()))?;
-        parser::parse_text(path, &text)
+        parser::parse_text(path.display(), &text, options)
+    }
+
+    /// Parses `genea.doc` contents that did not necessarily come from a file.
+    /// `origin` names where they came from in diagnostics.
+    pub fn from_genea_text(
+        origin: impl std::fmt::Display,
+        text: &str,
+        options: ParseOptions,
+    ) -> anyhow::Result<Self> {
+        parser::parse_text(origin, text, options)
     }
 
     fn add_person(&mut self, person_data: PersonData) -> Person {
@@ -33,6 +86,45 @@ impl Genea {
         Partnership(len)
     }
 
+    /// Removes `person` from the tree, putting `replacement` in their place in every
+    /// partnership. The people after `person` shift down by one, so the returned function
+    /// maps a `Person` from before the removal to the same person afterwards.
+    fn replace_person(&mut self, person: Person, replacement: Person) -> impl Fn(Person) -> Person {
+        let renumber = move |p: Person| {
+            let p = if p == person { replacement } else { p };
+            if p.0 > person.0 {
+                Person(p.0 - 1)
+            } else {
+                p
+            }
+        };
+
+        let removed = self.people.remove(person.0);
+        let replacement_data = &mut self[renumber(replacement)];
+        if replacement_data.child_in.is_none() {
+            replacement_data.child_in = removed.child_in;
+        }
+        for partnership in removed.parent_in {
+            if !replacement_data.parent_in.contains(&partnership) {
+                replacement_data.parent_in.push(partnership);
+            }
+        }
+
+        for partnership_data in &mut self.partnerships {
+            partnership_data.parents = partnership_data
+                .parents
+                .iter()
+                .map(|&p| renumber(p))
+                .collect();
+            partnership_data.primary_parent = renumber(partnership_data.primary_parent);
+            for child in &mut partnership_data.children {
+                *child = renumber(*child);
+            }
+        }
+
+        renumber
+    }
+
     pub fn partnerships(&self) -> impl Iterator<Item = Partnership> {
         (0..self.partnerships.len()).map(Partnership)
     }
@@ -44,18 +136,47 @@ impl Genea {
 
     /// Iterator over all the `Person` values
     pub fn root_people(&self) -> impl Iterator<Item = Person> + '_ {
-        self.people().filter(|&person| {
-            self[person]
-                .henry_number
-                .as_ref()
-                .map(|h| h.is_root_ancestor())
-                .unwrap_or(false)
-        })
+        self.people()
+            .filter(|&person| self[person].is_root_ancestor())
+    }
+
+    /// A stable identifier for `person`, used in URLs and the JSON API. People with a
+    /// persistent id are identified by it; otherwise, people with a henry number are
+    /// identified by that. Spouses from outside the family are identified by their
+    /// partner's henry number and their spousal index (e.g., `1-2--1`); if they partnered more
+    /// than one family member, the smallest such pair is used.
+    pub fn id(&self, person: Person) -> String {
+        let person_data = &self[person];
+        if let Some(id) = &person_data.persistent_id {
+            return id.clone();
+        }
+
+        if let Some(hn) = &person_data.henry_number {
+            return hn.to_string();
+        }
+
+        person_data
+            .parent_in
+            .iter()
+            .filter_map(|&partnership| {
+                let partner = self[partnership].other_parent(person)?;
+                let hn = self[partner].henry_number()?;
+                Some((hn, self[partnership].spousal_index?))
+            })
+            .min()
+            .map(|(hn, spousal_index)| format!("{hn}--{spousal_index}"))
+            .unwrap_or_else(|| format!("person-{}", person.0))
     }
 
     pub fn maintainer_link(&self) -> &Option<String> {
         &self.maintainer_link
     }
+
+    /// Problems found in the file that did not stop it from being parsed, such as
+    /// counts of kids and spouses that don't match the tree.
+    pub fn warnings(&self) -> &[ParseError] {
+        &self.warnings
+    }
 }
 
 impl std::fmt::Debug for Genea {
@@ -143,6 +264,20 @@ pub struct Span {
     chars: Option<(usize, usize)>,
 }
 
+/// Serialized as the (1-based) line number plus the byte offsets within that line
+/// where the span starts and ends. The offsets are null if the span covers the whole line.
+impl serde::Serialize for Span {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut s = serializer.serialize_struct("Span", 3)?;
+        s.serialize_field("line", &self.line_num)?;
+        s.serialize_field("start", &self.chars.map(|(start, _)| start))?;
+        s.serialize_field("end", &self.chars.map(|(_, end)| end))?;
+        s.end()
+    }
+}
+
 /// Index of an individual
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Person(usize);
@@ -181,7 +316,7 @@ impl HenryNumber {
     }
 
     fn parent(&self) -> Option<HenryNumber> {
-        if self.is_root_ancestor() {
+        if self.ancestry.len() <= 1 {
             None
         } else {
             Some(Self {
@@ -190,10 +325,12 @@ impl HenryNumber {
         }
     }
 
-    fn next_sibling(&self) -> Self {
+    /// The henry number of the next younger sibling, if it can be written down.
+    fn next_sibling(&self) -> Option<Self> {
         let mut ancestry = self.ancestry.clone();
-        *ancestry.last_mut().unwrap() += 1;
-        Self { ancestry }
+        let last = ancestry.last_mut().unwrap();
+        *last = last.checked_add(1)?;
+        Some(Self { ancestry })
     }
 }
 
@@ -226,6 +363,12 @@ impl SpousalIndex {
     }
 }
 
+impl std::fmt::Display for SpousalIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[derive(Debug)]
 pub struct PersonData {
     /// The offset of the individual's *name*
@@ -240,13 +383,39 @@ pub struct PersonData {
     pub child_in: Option<Partnership>,
     pub parent_in: Vec<Partnership>,
     pub name: String,
+
+    /// Other spellings of the name (e.g., transliterations used in different branches),
+    /// written after the name as `Name|Alias`. Lines match if they share any spelling.
+    pub aliases: Vec<String>,
+
+    /// An identifier that stays the same when the family is renumbered, written as
+    /// `#id` before the name. See [`Genea::id`].
+    pub persistent_id: Option<String>,
+
     pub comments: String,
+
+    /// Whether the line has a `\` with nothing after it, which may mark a comment
+    /// still to be written
+    pub blank_comments: bool,
+
     pub private_comments: String,
+
+    /// The counts declared on the person's line, which come from the paper records
+    /// and so may not match the tree
     pub num_spouses: usize,
     pub num_kids: usize,
 }
 
 impl PersonData {
+    /// True if `name` is this person's name or one of their aliases,
+    /// ignoring differences in how they were typed (see [`normalize_name`]).
+    pub fn is_named(&self, name: &str) -> bool {
+        let name = normalize_name(name);
+        std::iter::once(&self.name)
+            .chain(&self.aliases)
+            .any(|n| normalize_name(n) == name)
+    }
+
     pub fn henry_number(&self) -> Option<&HenryNumber> {
         self.henry_number.as_ref()
     }
@@ -260,10 +429,39 @@ impl PersonData {
     }
 }
 
+/// The form of a name used when comparing names. Names typed on different machines
+/// may use different (but equivalent) unicode sequences for accented letters, different
+/// apostrophes (`T'Melis` vs `T’Melis`) or different amounts of whitespace.
+fn normalize_name(name: &str) -> String {
+    use unicode_normalization::UnicodeNormalization;
+
+    name.nfc()
+        .map(|c| match c {
+            '\u{2018}' | '\u{2019}' | '\u{02BC}' | '\u{0060}' | '\u{00B4}' | '\u{2032}' => '\'',
+            c => c,
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .join(" ")
+}
+
 #[derive(Debug)]
 pub struct PartnershipData {
     pub parents: BTreeSet<Person>,
+
+    /// The parent whose line the partnership is listed under in `genea.doc`
+    /// (the children's henry numbers extend theirs)
+    pub primary_parent: Person,
+
     pub children: Vec<Person>,
+
+    /// The spousal index declared on the spouse line that introduced this partnership
+    /// (`None` if the children were listed without a spouse)
+    pub spousal_index: Option<SpousalIndex>,
+
+    /// The number of kids declared on that spouse line, which comes from the paper
+    /// records and so may not match `children`
+    pub num_kids: usize,
 }
 
 impl PartnershipData {
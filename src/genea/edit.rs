@@ -0,0 +1,635 @@
+//! Editing a family tree while keeping it consistent: everybody's `child_in` and
+//! `parent_in` agree with the partnerships, henry numbers follow the order of the
+//! children and spouses are numbered in sequence. The declared counts of kids and
+//! spouses go up and down with the tree, but are otherwise left as they are, since they
+//! come from the paper records. Use [`Genea::to_genea_doc`] to save the result, as the
+//! `insert` and `move` commands do.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use super::{
+    fix, Gender, Genea, HenryNumber, ParseOptions, Partnership, PartnershipData, Person,
+    PersonData, Span, SpousalIndex,
+};
+
+#[cfg(test)]
+mod tests;
+
+impl Genea {
+    /// The descendant with the given henry number, written as in the site (e.g., `1-1-2`).
+    pub fn descendant(&self, henry_number: &str) -> anyhow::Result<Person> {
+        let ancestry = henry_number
+            .split('-')
+            .map(|n| match n.trim().parse() {
+                Ok(0) | Err(_) => anyhow::bail!(
+                    "`{henry_number}` is not a henry number; expected something like `1-1-2`"
+                ),
+                Ok(n) => Ok(n),
+            })
+            .collect::<anyhow::Result<Vec<usize>>>()?;
+        let henry_number = HenryNumber { ancestry };
+
+        self.people()
+            .find(|&p| self[p].henry_number.as_ref() == Some(&henry_number))
+            .ok_or_else(|| anyhow::anyhow!("nobody has the henry number {henry_number}"))
+    }
+
+    /// Adds a child named `name` to `partnership`. They become the `position`th child
+    /// (counting from 1) of the partnership's primary parent, whose later children are
+    /// renumbered, along with all of their descendants, to make room.
+    pub fn add_child(
+        &mut self,
+        partnership: Partnership,
+        position: usize,
+        name: &str,
+        gender: Gender,
+    ) -> anyhow::Result<Person> {
+        let parent = self[partnership].primary_parent;
+        if self[parent].henry_number.is_none() {
+            anyhow::bail!(
+                "{} is not a descendant, so children can't be listed under them",
+                self[parent].name
+            );
+        }
+
+        let mut children = self.numbered_children(parent);
+        if position == 0 || position > children.len() + 1 {
+            anyhow::bail!(
+                "a new child of {} can go in positions 1 to {}",
+                self[parent].name,
+                children.len() + 1
+            );
+        }
+
+        let child = self.add_person(new_person_data(name, gender)?);
+        self[child].child_in = Some(partnership);
+        self[partnership].children.push(child);
+        self[partnership].num_kids += 1;
+
+        children.insert(position - 1, child);
+        self.renumber_children(parent, &children);
+        for p in self[partnership].parents.clone() {
+            self[p].num_kids += 1;
+        }
+
+        Ok(child)
+    }
+
+    /// Adds a spouse named `name` for `person`, who must be a descendant (i.e., have a
+    /// henry number). The spouse is numbered after any existing spouses of `person`.
+    pub fn add_spouse(
+        &mut self,
+        person: Person,
+        name: &str,
+        gender: Gender,
+    ) -> anyhow::Result<(Person, Partnership)> {
+        if self[person].henry_number.is_none() {
+            anyhow::bail!(
+                "{} is not a descendant, so spouses can't be listed under them",
+                self[person].name
+            );
+        }
+
+        let spouse = self.add_person(new_person_data(name, gender)?);
+        let partnership = self.add_partner(person, spouse)?;
+        Ok((spouse, partnership))
+    }
+
+    /// Partners `person`, who must be a descendant, with somebody already in the tree,
+    /// listing the partnership under `person`. The partner is numbered after any existing
+    /// spouses of `person`.
+    pub fn add_partner(&mut self, person: Person, partner: Person) -> anyhow::Result<Partnership> {
+        if self[person].henry_number.is_none() {
+            anyhow::bail!(
+                "{} is not a descendant, so spouses can't be listed under them",
+                self[person].name
+            );
+        }
+
+        if person == partner {
+            anyhow::bail!("{} can't be their own spouse", self[person].name);
+        }
+
+        let parents: BTreeSet<Person> = [person, partner].into_iter().collect();
+        if self.partnerships().any(|p| self[p].parents == parents) {
+            anyhow::bail!(
+                "{} and {} are already partners",
+                self[person].name,
+                self[partner].name
+            );
+        }
+
+        let spousal_index = SpousalIndex(self.spouse_partnerships(person).len() + 1);
+        let partnership = self.add_partnership(PartnershipData {
+            parents,
+            primary_parent: person,
+            children: vec![],
+            spousal_index: Some(spousal_index),
+            num_kids: 0,
+        });
+        self[person].parent_in.push(partnership);
+        self[partner].parent_in.push(partnership);
+
+        self[person].num_spouses += 1;
+        Ok(partnership)
+    }
+
+    /// Puts the children of `parent` (see [`Genea::numbered_children`]) in the order given
+    /// by `children`, renumbering them and their descendants to match.
+    pub fn reorder_children(&mut self, parent: Person, children: &[Person]) -> anyhow::Result<()> {
+        let existing: BTreeSet<Person> = self.numbered_children(parent).into_iter().collect();
+        let reordered: BTreeSet<Person> = children.iter().copied().collect();
+        if existing != reordered || children.len() != existing.len() {
+            anyhow::bail!(
+                "the new order must list each of the children of {} exactly once",
+                self[parent].name
+            );
+        }
+
+        self.renumber_children(parent, children);
+        Ok(())
+    }
+
+    /// Replaces the comments on `person`, which are shown on the site.
+    pub fn set_comments(&mut self, person: Person, comments: &str) -> anyhow::Result<()> {
+        if let Some(c) = comments.chars().find(|&c| c == ';' || c == '\n') {
+            anyhow::bail!("comments can't contain {c:?}; it would end the comments");
+        }
+
+        let person_data = &mut self[person];
+        person_data.comments = comments.trim().to_string();
+        person_data.blank_comments = false;
+        Ok(())
+    }
+
+    /// Replaces the private comments on `person`, which are not shown on the site.
+    pub fn set_private_comments(&mut self, person: Person, comments: &str) -> anyhow::Result<()> {
+        if comments.contains('\n') {
+            anyhow::bail!("private comments must fit on one line");
+        }
+
+        self[person].private_comments = comments.trim().to_string();
+        Ok(())
+    }
+
+    /// Makes `child` the `position`th child (counting from 1) of their parent, renumbering
+    /// their siblings in between, and all of their descendants, to match.
+    pub fn move_child(&mut self, child: Person, position: usize) -> anyhow::Result<()> {
+        let Some(partnership) = self[child].child_in else {
+            anyhow::bail!("{} has no parents in the tree", self[child].name);
+        };
+        let parent = self[partnership].primary_parent;
+
+        let mut children = self.numbered_children(parent);
+        if position == 0 || position > children.len() {
+            anyhow::bail!(
+                "{} has {} children, so {} can go in positions 1 to {}",
+                self[parent].name,
+                children.len(),
+                self[child].name,
+                children.len()
+            );
+        }
+
+        children.retain(|&c| c != child);
+        children.insert(position - 1, child);
+        self.reorder_children(parent, &children)
+    }
+
+    /// The partnership that new children of `parent` should be added to: the one with the
+    /// spouse with the given spousal index, or, if there is none, the only partnership
+    /// listed under `parent`. If there are no partnerships listed under `parent`, a new one
+    /// is made for children listed without a spouse.
+    pub fn partnership_for_children(
+        &mut self,
+        parent: Person,
+        spousal_index: Option<usize>,
+    ) -> anyhow::Result<Partnership> {
+        if self[parent].henry_number.is_none() {
+            anyhow::bail!(
+                "{} is not a descendant, so children can't be listed under them",
+                self[parent].name
+            );
+        }
+
+        let listed: Vec<Partnership> = self[parent]
+            .parent_in
+            .iter()
+            .copied()
+            .filter(|&p| self[p].primary_parent == parent)
+            .collect();
+
+        if let Some(spousal_index) = spousal_index {
+            return listed
+                .into_iter()
+                .find(|&p| self[p].spousal_index == Some(SpousalIndex(spousal_index)))
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "{} has no spouse with spousal index {spousal_index}",
+                        self[parent].name
+                    )
+                });
+        }
+
+        match listed[..] {
+            [] => {
+                let partnership = self.add_partnership(PartnershipData {
+                    parents: [parent].into_iter().collect(),
+                    primary_parent: parent,
+                    children: vec![],
+                    spousal_index: None,
+                    num_kids: 0,
+                });
+                self[parent].parent_in.push(partnership);
+                Ok(partnership)
+            }
+            [partnership] => Ok(partnership),
+            _ => anyhow::bail!(
+                "{} has several spouses, so the other parent must be given by their spousal index",
+                self[parent].name
+            ),
+        }
+    }
+
+    /// Removes `person` together with everybody listed under them: their descendants and
+    /// the spouses of those, unless a spouse is also partnered with somebody who stays.
+    /// If `person` was somebody's child, their younger siblings are renumbered to close the
+    /// gap; if they were somebody's spouse, the later spouses are.
+    ///
+    /// Fails if one of the people being removed is a parent in a partnership listed under
+    /// somebody who stays, since their children would be left with only one parent.
+    ///
+    /// People and partnerships that remain are renumbered, so existing `Person` and
+    /// `Partnership` values are no longer valid; the returned function maps each `Person`
+    /// from before the removal to the same person afterwards (or `None` if they were removed).
+    pub fn delete_subtree(
+        &mut self,
+        person: Person,
+    ) -> anyhow::Result<impl Fn(Person) -> Option<Person>> {
+        let mut removed_people = BTreeSet::new();
+        let mut removed_partnerships = BTreeSet::new();
+        self.collect_subtree(person, &mut removed_people, &mut removed_partnerships)?;
+
+        // Spouses go too, unless they have ties to the rest of the tree.
+        for &partnership in &removed_partnerships {
+            for &parent in &self[partnership].parents {
+                if self[parent].henry_number.is_none()
+                    && self[parent]
+                        .parent_in
+                        .iter()
+                        .all(|p| removed_partnerships.contains(p))
+                {
+                    removed_people.insert(parent);
+                }
+            }
+        }
+
+        let child_in = self[person]
+            .child_in
+            .filter(|p| !removed_partnerships.contains(p));
+        let parent = child_in.map(|p| self[p].primary_parent);
+        if let Some(partnership) = child_in {
+            let partnership_data = &mut self[partnership];
+            partnership_data.num_kids = partnership_data.num_kids.saturating_sub(1);
+        }
+
+        // Everybody who stays but loses children or spouses declares that many fewer.
+        let mut lost: BTreeMap<Person, (usize, usize)> = BTreeMap::new();
+        for stays in self.people().filter(|p| !removed_people.contains(p)) {
+            let lost_kids = self[stays]
+                .parent_in
+                .iter()
+                .flat_map(|&p| &self[p].children)
+                .filter(|c| removed_people.contains(c))
+                .count();
+            let lost_spouses = self
+                .spouse_partnerships(stays)
+                .iter()
+                .filter(|p| removed_partnerships.contains(p))
+                .count();
+            if lost_kids > 0 || lost_spouses > 0 {
+                lost.insert(stays, (lost_kids, lost_spouses));
+            }
+        }
+
+        let renumber = self.remove(&removed_people, &removed_partnerships);
+
+        if let Some(parent) = parent.and_then(&renumber) {
+            let children = self.numbered_children(parent);
+            self.renumber_children(parent, &children);
+        }
+
+        for (person, (lost_kids, lost_spouses)) in lost {
+            let person = renumber(person).unwrap();
+            self.renumber_spouses(person);
+            let person_data = &mut self[person];
+            person_data.num_kids = person_data.num_kids.saturating_sub(lost_kids);
+            person_data.num_spouses = person_data.num_spouses.saturating_sub(lost_spouses);
+        }
+
+        Ok(renumber)
+    }
+
+    /// The children whose henry numbers extend that of `parent`, i.e., the children of the
+    /// partnerships listed under `parent`, in order of their henry numbers.
+    pub fn numbered_children(&self, parent: Person) -> Vec<Person> {
+        let mut children: Vec<Person> = self[parent]
+            .parent_in
+            .iter()
+            .filter(|&&p| self[p].primary_parent == parent)
+            .flat_map(|&p| self[p].children.iter().copied())
+            .collect();
+        children.sort_by_key(|&c| self[c].henry_number.clone());
+        children
+    }
+
+    /// The partnerships with a spouse listed under `person`, in order of their spousal index.
+    fn spouse_partnerships(&self, person: Person) -> Vec<Partnership> {
+        let mut partnerships: Vec<Partnership> = self[person]
+            .parent_in
+            .iter()
+            .copied()
+            .filter(|&p| self[p].primary_parent == person && self[p].other_parent(person).is_some())
+            .collect();
+        partnerships.sort_by_key(|&p| self[p].spousal_index);
+        partnerships
+    }
+
+    /// Gives the children of `parent` the henry numbers that follow from their
+    /// position in `children`, renumbering their descendants to match.
+    fn renumber_children(&mut self, parent: Person, children: &[Person]) {
+        let parent_hn = self[parent].henry_number.clone().unwrap();
+
+        let moves: Vec<(Option<HenryNumber>, HenryNumber)> = children
+            .iter()
+            .zip(1..)
+            .map(|(&child, n)| {
+                let mut ancestry = parent_hn.ancestry.clone();
+                ancestry.push(n);
+                (self[child].henry_number.clone(), HenryNumber { ancestry })
+            })
+            .collect();
+
+        // Renumber all of the descendants at once, so that the old and new
+        // henry numbers of different children can't get mixed up.
+        for person_data in &mut self.people {
+            let Some(hn) = &mut person_data.henry_number else {
+                continue;
+            };
+            if let Some((Some(old), new)) = moves
+                .iter()
+                .find(|(old, _)| old.as_ref().is_some_and(|old| old.is_prefix_of(hn)))
+            {
+                let mut ancestry = new.ancestry.clone();
+                ancestry.extend(&hn.ancestry[old.ancestry.len()..]);
+                *hn = HenryNumber { ancestry };
+            }
+        }
+
+        for (&child, (_, new)) in children.iter().zip(moves) {
+            self[child].henry_number = Some(new);
+        }
+
+        for &partnership in &self[parent].parent_in.clone() {
+            let mut partnership_children = std::mem::take(&mut self[partnership].children);
+            partnership_children.sort_by_key(|&c| self[c].henry_number.clone());
+            self[partnership].children = partnership_children;
+        }
+    }
+
+    /// Numbers the spouses listed under `person` 1, 2, ... in their current order.
+    fn renumber_spouses(&mut self, person: Person) {
+        for (partnership, n) in self.spouse_partnerships(person).into_iter().zip(1..) {
+            self[partnership].spousal_index = Some(SpousalIndex(n));
+        }
+    }
+
+    /// Collects `person` and everybody listed under them, along with the partnerships
+    /// that go with them.
+    fn collect_subtree(
+        &self,
+        person: Person,
+        people: &mut BTreeSet<Person>,
+        partnerships: &mut BTreeSet<Partnership>,
+    ) -> anyhow::Result<()> {
+        people.insert(person);
+
+        for &partnership in &self[person].parent_in {
+            let primary_parent = self[partnership].primary_parent;
+            if primary_parent != person && !people.contains(&primary_parent) {
+                if !self[partnership].children.is_empty() {
+                    anyhow::bail!(
+                        "{} has children with {}, who would be left without a partner",
+                        self[person].name,
+                        self[primary_parent].name
+                    );
+                }
+            } else {
+                for &child in &self[partnership].children {
+                    self.collect_subtree(child, people, partnerships)?;
+                }
+            }
+            partnerships.insert(partnership);
+        }
+
+        Ok(())
+    }
+
+    /// Removes the given people and partnerships, and everything that refers to them.
+    /// The rest are renumbered; the returned function maps each `Person` from before
+    /// to the same person afterwards.
+    fn remove(
+        &mut self,
+        people: &BTreeSet<Person>,
+        partnerships: &BTreeSet<Partnership>,
+    ) -> impl Fn(Person) -> Option<Person> {
+        let person_map = index_map(self.people().map(|p| !people.contains(&p)));
+        let partnership_map = index_map(self.partnerships().map(|p| !partnerships.contains(&p)));
+
+        self.people = std::mem::take(&mut self.people)
+            .into_iter()
+            .zip(&person_map)
+            .filter_map(|(person_data, new_index)| new_index.map(|_| person_data))
+            .collect();
+        self.partnerships = std::mem::take(&mut self.partnerships)
+            .into_iter()
+            .zip(&partnership_map)
+            .filter_map(|(partnership_data, new_index)| new_index.map(|_| partnership_data))
+            .collect();
+
+        let renumber_person = move |p: Person| person_map[p.0].map(Person);
+        let renumber_partnership = move |p: Partnership| partnership_map[p.0].map(Partnership);
+
+        for person_data in &mut self.people {
+            person_data.child_in = person_data.child_in.and_then(&renumber_partnership);
+            person_data.parent_in = person_data
+                .parent_in
+                .iter()
+                .filter_map(|&p| renumber_partnership(p))
+                .collect();
+        }
+
+        for partnership_data in &mut self.partnerships {
+            partnership_data.parents = partnership_data
+                .parents
+                .iter()
+                .filter_map(|&p| renumber_person(p))
+                .collect();
+            partnership_data.children = partnership_data
+                .children
+                .iter()
+                .filter_map(|&p| renumber_person(p))
+                .collect();
+            partnership_data.primary_parent =
+                renumber_person(partnership_data.primary_parent).unwrap();
+        }
+
+        renumber_person
+    }
+}
+
+/// The data for somebody new, who has not been placed in the tree yet. Fails if `name`
+/// can't be written on a person line.
+fn new_person_data(name: &str, gender: Gender) -> anyhow::Result<PersonData> {
+    let name = name.trim();
+    if name.is_empty() {
+        anyhow::bail!("a name can't be empty");
+    }
+    if let Some(c) = name
+        .chars()
+        .next()
+        .filter(|c| *c == '#' || c.is_ascii_digit())
+    {
+        anyhow::bail!("a name can't start with {c:?}");
+    }
+    if let Some(c) = name.chars().find(|c| "\\;|\n".contains(*c)) {
+        anyhow::bail!("a name can't contain {c:?}");
+    }
+
+    Ok(PersonData {
+        span: Span {
+            line_num: 0,
+            chars: None,
+        },
+        henry_number: None,
+        gender,
+        child_in: None,
+        parent_in: vec![],
+        name: name.to_string(),
+        aliases: vec![],
+        persistent_id: None,
+        comments: String::new(),
+        blank_comments: false,
+        private_comments: String::new(),
+        num_spouses: 0,
+        num_kids: 0,
+    })
+}
+
+/// For each index, its new index once the ones that are not kept are removed.
+fn index_map(keep: impl Iterator<Item = bool>) -> Vec<Option<usize>> {
+    let mut next = 0;
+    keep.map(|keep| {
+        keep.then(|| {
+            next += 1;
+            next - 1
+        })
+    })
+    .collect()
+}
+
+/// Adds a child named `name` to the `genea.doc` file at `path`, under the descendant with
+/// henry number `under` and the spouse with `spousal_index` (see
+/// [`Genea::partnership_for_children`]). They become the `position`th child, or the
+/// last one if `position` is `None`.
+///
+/// The later siblings and their descendants are renumbered, which is done by regenerating
+/// the file (see [`Genea::to_genea_doc`]) so that alternate henry numbers pointing at
+/// them are updated too. That only touches the lines that changed if the file is already
+/// laid out the way it is regenerated, so other files are refused (see
+/// [`read_genea_doc_for_editing`]). With `dry_run`, the changes are printed as a diff instead.
+pub fn insert_child_into_genea_doc(
+    path: &Path,
+    under: &str,
+    spousal_index: Option<usize>,
+    position: Option<usize>,
+    name: &str,
+    gender: Gender,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    edit_genea_doc(path, dry_run, |genea| {
+        let parent = genea.descendant(under)?;
+        let partnership = genea.partnership_for_children(parent, spousal_index)?;
+        let position = position.unwrap_or(genea.numbered_children(parent).len() + 1);
+        let child = genea.add_child(partnership, position, name, gender)?;
+        Ok(format!(
+            "inserted {name} as {}",
+            genea[child].henry_number.as_ref().unwrap()
+        ))
+    })
+}
+
+/// Moves the descendant with henry number `henry_number` in the `genea.doc` file at `path`
+/// to be the `position`th child of their parent, renumbering their siblings in between
+/// and all of their descendants like [`insert_child_into_genea_doc`].
+pub fn move_child_in_genea_doc(
+    path: &Path,
+    henry_number: &str,
+    position: usize,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    edit_genea_doc(path, dry_run, |genea| {
+        let child = genea.descendant(henry_number)?;
+        genea.move_child(child, position)?;
+        Ok(format!(
+            "moved {} to {}",
+            genea[child].name,
+            genea[child].henry_number.as_ref().unwrap()
+        ))
+    })
+}
+
+/// Applies `edit` to the tree in the `genea.doc` file at `path` and writes the result back,
+/// reporting the message returned by `edit`. With `dry_run`, the changes are printed as a
+/// diff instead.
+fn edit_genea_doc(
+    path: &Path,
+    dry_run: bool,
+    edit: impl FnOnce(&mut Genea) -> anyhow::Result<String>,
+) -> anyhow::Result<()> {
+    let (text, mut genea) = read_genea_doc_for_editing(path)?;
+    let message = edit(&mut genea)?;
+    let edited = genea.to_genea_doc()?;
+
+    if dry_run {
+        print!("{}", fix::diff(path, &text, &edited));
+    } else {
+        std::fs::write(path, &edited)?;
+        eprintln!("{message}");
+    }
+
+    Ok(())
+}
+
+/// Reads the `genea.doc` file at `path`, returning its text and the tree it describes.
+/// Edits are saved by regenerating the whole file, so this fails if the file is not
+/// already laid out that way: saving would then change lines that were not edited,
+/// burying the edit in a diff of the whole file.
+pub fn read_genea_doc_for_editing(path: &Path) -> anyhow::Result<(String, Genea)> {
+    let text = std::fs::read_to_string(path)?;
+    let genea = Genea::from_genea_text(path.display(), &text, ParseOptions::default())?;
+
+    if genea.to_genea_doc()? != text {
+        anyhow::bail!(
+            "`{path}` is not laid out the way `family-tree write` would write it, so saving \
+             an edit would change other lines too; run `family-tree write {path} {path}` \
+             first and commit that separately",
+            path = path.display()
+        );
+    }
+
+    Ok((text, genea))
+}
@@ -0,0 +1,386 @@
+//! Edits should leave the tree consistent: the partnerships and the people agree on who
+//! belongs to which, henry numbers and spousal indices are in sequence and the tree can
+//! be written out and read back in without warnings.
+
+use std::path::PathBuf;
+
+use super::{insert_child_into_genea_doc, move_child_in_genea_doc, read_genea_doc_for_editing};
+use crate::genea::{
+    testing::GENEA_DOC, Gender, Genea, HenryNumber, ParseOptions, Partnership, Person,
+};
+
+/// Sarek's family, with counts that match the tree.
+const FAMILY: &str = "\
+ 1 0 M 3 2 0 Sarek
+ 1 0 F 2 0 1 Amanda
+ 1 1 M 1 2 0 Spock
+ 1 1 F 0 0 1 T'Pring
+ 1 1 F 1 0 2 Leila
+ 1 1 1 M 0 0 0 Saavik
+ 1 2 F 0 0 0 Michael
+ 1 0 F 1 0 2 T'Rea
+ 1 3 M 0 0 0 Sybok
+";
+
+fn family() -> Genea {
+    Genea::from_genea_text("genea.doc", FAMILY, ParseOptions::default()).unwrap()
+}
+
+fn named(genea: &Genea, name: &str) -> Person {
+    genea.people().find(|&p| genea[p].name == name).unwrap()
+}
+
+/// The partnership of the spouse named `name` with the person they are listed under.
+fn partnership_of(genea: &Genea, name: &str) -> Partnership {
+    let [partnership] = genea[named(genea, name)].parent_in[..] else {
+        panic!("expected {name} to have one partnership");
+    };
+    partnership
+}
+
+fn henry_number(genea: &Genea, name: &str) -> String {
+    genea[named(genea, name)]
+        .henry_number
+        .as_ref()
+        .unwrap()
+        .to_string()
+}
+
+fn assert_consistent(genea: &Genea) {
+    for person in genea.people() {
+        if let Some(partnership) = genea[person].child_in {
+            assert!(genea[partnership].children.contains(&person));
+        }
+        for &partnership in &genea[person].parent_in {
+            assert!(genea[partnership].parents.contains(&person));
+        }
+    }
+
+    for partnership in genea.partnerships() {
+        let partnership_data = &genea[partnership];
+        assert!(partnership_data
+            .parents
+            .contains(&partnership_data.primary_parent));
+        for &child in &partnership_data.children {
+            assert_eq!(genea[child].child_in, Some(partnership));
+        }
+        for &parent in &partnership_data.parents {
+            assert!(genea[parent].parent_in.contains(&partnership));
+        }
+    }
+
+    for person in genea.people() {
+        let Some(hn) = &genea[person].henry_number else {
+            continue;
+        };
+        for (child, n) in genea.numbered_children(person).into_iter().zip(1..) {
+            let ancestry = [&hn.ancestry[..], &[n]].concat();
+            assert_eq!(genea[child].henry_number, Some(HenryNumber { ancestry }));
+        }
+        for (partnership, n) in genea.spouse_partnerships(person).into_iter().zip(1..) {
+            assert_eq!(genea[partnership].spousal_index.unwrap().0, n);
+        }
+    }
+
+    // The counts in `FAMILY` match the tree, so they should still match it after the edit.
+    let text = genea.to_genea_doc().unwrap();
+    let reparsed = Genea::from_genea_text("<edited>", &text, ParseOptions::default())
+        .unwrap_or_else(|e| panic!("{e}\nin:\n{text}"));
+    let warnings: Vec<String> = reparsed.warnings().iter().map(|w| w.to_string()).collect();
+    assert!(warnings.is_empty(), "{}\nin:\n{text}", warnings.join("\n"));
+}
+
+#[test]
+fn add_child_renumbers_younger_siblings_and_their_descendants() {
+    let mut genea = family();
+    let partnership = partnership_of(&genea, "Amanda");
+    genea
+        .add_child(partnership, 1, "Sybil", Gender::Female)
+        .unwrap();
+
+    assert_eq!(henry_number(&genea, "Sybil"), "1-1");
+    assert_eq!(henry_number(&genea, "Spock"), "1-2");
+    assert_eq!(henry_number(&genea, "Saavik"), "1-2-1");
+    assert_eq!(henry_number(&genea, "Michael"), "1-3");
+    assert_eq!(henry_number(&genea, "Sybok"), "1-4");
+    assert_eq!(genea[named(&genea, "Sarek")].num_kids, 4);
+    assert_eq!(genea[partnership].num_kids, 3);
+    assert_consistent(&genea);
+}
+
+#[test]
+fn add_child_only_fills_the_positions_up_to_the_last_child() {
+    let mut genea = family();
+    let partnership = partnership_of(&genea, "Amanda");
+    assert!(genea
+        .add_child(partnership, 0, "Sybil", Gender::Female)
+        .is_err());
+    assert!(genea
+        .add_child(partnership, 5, "Sybil", Gender::Female)
+        .is_err());
+    genea
+        .add_child(partnership, 4, "Sybil", Gender::Female)
+        .unwrap();
+    assert_eq!(henry_number(&genea, "Sybil"), "1-4");
+    assert_consistent(&genea);
+}
+
+#[test]
+fn reorder_children_renumbers_their_descendants() {
+    let mut genea = family();
+    let sarek = named(&genea, "Sarek");
+    let order = ["Sybok", "Michael", "Spock"].map(|name| named(&genea, name));
+    genea.reorder_children(sarek, &order).unwrap();
+
+    assert_eq!(henry_number(&genea, "Sybok"), "1-1");
+    assert_eq!(henry_number(&genea, "Michael"), "1-2");
+    assert_eq!(henry_number(&genea, "Spock"), "1-3");
+    assert_eq!(henry_number(&genea, "Saavik"), "1-3-1");
+    assert_consistent(&genea);
+}
+
+#[test]
+fn reorder_children_must_list_each_child_once() {
+    let mut genea = family();
+    let sarek = named(&genea, "Sarek");
+    let (spock, sybok) = (named(&genea, "Spock"), named(&genea, "Sybok"));
+    assert!(genea.reorder_children(sarek, &[spock, sybok]).is_err());
+    assert!(genea
+        .reorder_children(sarek, &[spock, sybok, sybok])
+        .is_err());
+    assert_eq!(henry_number(&genea, "Sybok"), "1-3");
+    assert_consistent(&genea);
+}
+
+#[test]
+fn add_spouse_numbers_them_after_the_other_spouses() {
+    let mut genea = family();
+    let sarek = named(&genea, "Sarek");
+    let (_, partnership) = genea.add_spouse(sarek, "Perrin", Gender::Female).unwrap();
+
+    assert_eq!(genea[partnership].spousal_index.unwrap().0, 3);
+    assert_eq!(genea[sarek].num_spouses, 3);
+    assert_consistent(&genea);
+}
+
+#[test]
+fn add_partner_refuses_partnerships_that_exist_or_make_no_sense() {
+    let mut genea = family();
+    let (sarek, amanda) = (named(&genea, "Sarek"), named(&genea, "Amanda"));
+    assert!(genea.add_partner(sarek, amanda).is_err());
+    assert!(genea.add_partner(sarek, sarek).is_err());
+    assert!(genea.add_partner(amanda, named(&genea, "Sybok")).is_err());
+    assert_consistent(&genea);
+}
+
+#[test]
+fn delete_subtree_removes_descendants_and_their_spouses() {
+    let mut genea = family();
+    let michael = named(&genea, "Michael");
+    let renumber = genea.delete_subtree(named(&genea, "Spock")).unwrap();
+
+    assert_eq!(genea[renumber(michael).unwrap()].name, "Michael");
+    for name in ["Spock", "T'Pring", "Leila", "Saavik"] {
+        assert!(genea.people().all(|p| genea[p].name != name), "{name}");
+    }
+    assert_eq!(henry_number(&genea, "Michael"), "1-1");
+    assert_eq!(henry_number(&genea, "Sybok"), "1-2");
+    assert_eq!(genea[named(&genea, "Sarek")].num_kids, 2);
+    assert_consistent(&genea);
+}
+
+#[test]
+fn delete_subtree_renumbers_later_spouses() {
+    let mut genea = family();
+    let (spock, leila) = (named(&genea, "Spock"), named(&genea, "Leila"));
+    let renumber = genea.delete_subtree(named(&genea, "T'Pring")).unwrap();
+
+    let (spock, leila) = (renumber(spock).unwrap(), renumber(leila).unwrap());
+    let partnership = genea[leila].parent_in[0];
+    assert_eq!(genea[partnership].spousal_index.unwrap().0, 1);
+    assert_eq!(genea[spock].num_spouses, 1);
+    assert_eq!(henry_number(&genea, "Saavik"), "1-1-1");
+    assert_consistent(&genea);
+}
+
+#[test]
+fn delete_subtree_keeps_children_with_both_parents() {
+    let mut genea = family();
+    assert!(genea.delete_subtree(named(&genea, "Amanda")).is_err());
+    assert_consistent(&genea);
+}
+
+#[test]
+fn declared_counts_that_dont_match_the_tree_are_only_adjusted() {
+    // Sarek is recorded with a child who is missing from the file.
+    let text = FAMILY.replacen(" 3 2 0 Sarek", " 4 2 0 Sarek", 1);
+    let mut genea = Genea::from_genea_text("genea.doc", &text, ParseOptions::default()).unwrap();
+
+    let partnership = partnership_of(&genea, "T'Rea");
+    genea
+        .add_child(partnership, 4, "Sybil", Gender::Female)
+        .unwrap();
+    let sarek = named(&genea, "Sarek");
+    assert_eq!(genea[sarek].num_kids, 5);
+
+    let renumber = genea.delete_subtree(named(&genea, "Spock")).unwrap();
+    assert_eq!(genea[renumber(sarek).unwrap()].num_kids, 4);
+}
+
+#[test]
+fn descendant_is_found_by_henry_number() {
+    let genea = family();
+    assert_eq!(genea[genea.descendant("1-1-1").unwrap()].name, "Saavik");
+    assert_eq!(genea[genea.descendant(" 1 - 3 ").unwrap()].name, "Sybok");
+    assert!(genea.descendant("1-4").is_err());
+    assert!(genea.descendant("1-0").is_err());
+    assert!(genea.descendant("1.1").is_err());
+}
+
+#[test]
+fn move_child_renumbers_the_siblings_in_between() {
+    let mut genea = family();
+    genea.move_child(named(&genea, "Sybok"), 1).unwrap();
+
+    assert_eq!(henry_number(&genea, "Sybok"), "1-1");
+    assert_eq!(henry_number(&genea, "Spock"), "1-2");
+    assert_eq!(henry_number(&genea, "Saavik"), "1-2-1");
+    assert_eq!(henry_number(&genea, "Michael"), "1-3");
+    assert_consistent(&genea);
+}
+
+#[test]
+fn move_child_only_moves_among_the_siblings() {
+    let mut genea = family();
+    assert!(genea.move_child(named(&genea, "Spock"), 4).is_err());
+    assert!(genea.move_child(named(&genea, "Spock"), 0).is_err());
+    assert!(genea.move_child(named(&genea, "Sarek"), 1).is_err());
+    assert_eq!(henry_number(&genea, "Spock"), "1-1");
+    assert_consistent(&genea);
+}
+
+#[test]
+fn partnership_for_children_picks_the_spouse_by_spousal_index() {
+    let mut genea = family();
+    let sarek = named(&genea, "Sarek");
+    assert_eq!(
+        genea.partnership_for_children(sarek, Some(2)).unwrap(),
+        partnership_of(&genea, "T'Rea")
+    );
+    assert!(genea.partnership_for_children(sarek, Some(3)).is_err());
+    assert!(genea.partnership_for_children(sarek, None).is_err());
+
+    let saavik = named(&genea, "Saavik");
+    let partnership = genea.partnership_for_children(saavik, None).unwrap();
+    genea
+        .add_child(partnership, 1, "Sybil", Gender::Female)
+        .unwrap();
+    assert_eq!(henry_number(&genea, "Sybil"), "1-1-1-1");
+
+    // Saavik was recorded without kids, so his count goes up from there.
+    assert_eq!(genea[saavik].num_kids, 1);
+}
+
+/// A `genea.doc` file in a directory of its own, removed when dropped.
+struct TempGeneaDoc(PathBuf);
+
+impl TempGeneaDoc {
+    fn new(name: &str, text: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("family-tree-edit-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("genea.doc");
+        std::fs::write(&path, text).unwrap();
+        TempGeneaDoc(path)
+    }
+
+    fn read(&self) -> Genea {
+        Genea::from_genea_doc(&self.0).unwrap()
+    }
+}
+
+impl Drop for TempGeneaDoc {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(self.0.parent().unwrap());
+    }
+}
+
+#[test]
+fn insert_and_move_save_the_edited_file() {
+    let file = TempGeneaDoc::new("insert", &family().to_genea_doc().unwrap());
+
+    insert_child_into_genea_doc(&file.0, "1", Some(2), None, "Sybil", Gender::Female, false)
+        .unwrap();
+    let genea = file.read();
+    assert_eq!(henry_number(&genea, "Sybil"), "1-4");
+    assert_eq!(
+        genea[named(&genea, "Sybil")].child_in,
+        Some(partnership_of(&genea, "T'Rea"))
+    );
+
+    move_child_in_genea_doc(&file.0, "1-4", 1, false).unwrap();
+    let genea = file.read();
+    assert_eq!(henry_number(&genea, "Sybil"), "1-1");
+    assert_eq!(henry_number(&genea, "Saavik"), "1-2-1");
+    assert_consistent(&genea);
+}
+
+#[test]
+fn dry_run_leaves_the_file_alone() {
+    let text = family().to_genea_doc().unwrap();
+    let file = TempGeneaDoc::new("dry-run", &text);
+    move_child_in_genea_doc(&file.0, "1-3", 1, true).unwrap();
+    assert_eq!(std::fs::read_to_string(&file.0).unwrap(), text);
+}
+
+#[test]
+fn only_files_laid_out_as_they_are_written_are_edited() {
+    let file = TempGeneaDoc::new("layout", GENEA_DOC);
+    read_genea_doc_for_editing(&file.0).unwrap();
+
+    let file = TempGeneaDoc::new("unformatted", FAMILY);
+    assert!(read_genea_doc_for_editing(&file.0).is_err());
+    assert!(move_child_in_genea_doc(&file.0, "1-3", 1, false).is_err());
+    assert_eq!(std::fs::read_to_string(&file.0).unwrap(), FAMILY);
+}
+
+#[test]
+fn names_must_fit_on_a_person_line() {
+    let mut genea = family();
+    let sarek = named(&genea, "Sarek");
+    let partnership = genea.partnership_for_children(sarek, Some(2)).unwrap();
+    for name in [
+        "",
+        " ",
+        "#p1",
+        "1 Spock",
+        "Spock|Sybok",
+        "Spock\\x",
+        "Spock;x",
+    ] {
+        assert!(
+            genea.add_child(partnership, 1, name, Gender::Male).is_err(),
+            "{name:?}"
+        );
+        assert!(
+            genea.add_spouse(sarek, name, Gender::Male).is_err(),
+            "{name:?}"
+        );
+    }
+    assert_consistent(&genea);
+}
+
+#[test]
+fn comments_must_fit_on_a_person_line() {
+    let mut genea = family();
+    let spock = named(&genea, "Spock");
+    assert!(genea.set_comments(spock, "half; human").is_err());
+    assert!(genea.set_comments(spock, "half\nhuman").is_err());
+    assert!(genea.set_private_comments(spock, "half\nhuman").is_err());
+
+    genea.set_comments(spock, " half human ").unwrap();
+    genea.set_private_comments(spock, "check; Vulcan").unwrap();
+    assert_eq!(genea[spock].comments, "half human");
+    assert_eq!(genea[spock].private_comments, "check; Vulcan");
+    assert_consistent(&genea);
+}
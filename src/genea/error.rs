@@ -1,89 +1,144 @@
 use annotate_snippets::{Level, Renderer, Snippet};
+use serde::Serialize;
 use std::fmt::{Display, Write};
-use std::path::PathBuf;
+use std::sync::Arc;
 use thiserror::Error;
 
-use super::{HenryNumber, Span};
+use super::{
+    fix::{Edit, Replacement},
+    source_map::SourceMap,
+    tokenizer::LineField,
+    Gender, HenryNumber, Span, SpousalIndex,
+};
 
 #[derive(Error, Debug)]
 pub struct ParseError {
-    pub path: PathBuf,
+    pub source_map: Arc<SourceMap>,
     pub line_num: usize,
     pub kind: ParseErrorKind,
 }
 
+/// All the errors found while parsing a file.
+#[derive(Error, Debug)]
+pub struct ParseErrors {
+    pub source_map: Arc<SourceMap>,
+    pub errors: Vec<ParseError>,
+}
+
 #[derive(Error, Debug)]
 pub enum ParseErrorKind {
     #[error("line is malformed, regular expression doesn't match")]
     MalformedLine,
 
-    #[error(
-        "Child {line_name} has wrong henry number relative to their (Supposed) parent {top_name}"
-    )]
-    TopNotParent {
-        line_name: String,
+    #[error("malformed {field}, expected {expected} but found {found}")]
+    MalformedField {
+        /// The first field on the line that could not be read
+        field: LineField,
 
-        line_name_span: Span,
+        /// Span of the offending text (empty at the end of the line if the field is missing)
+        span: Span,
 
-        /// Henry number of the line that appears misplaced
-        line_hn: HenryNumber,
+        /// Description of what we expected to find
+        expected: String,
 
-        /// Span of the henry number on the line that appears misplaced
-        line_hn_span: Span,
+        /// Description of what we found instead
+        found: String,
+    },
 
-        /// Name of the person on the top of the stack
-        top_name: String,
+    #[error("alternate henry number `{alt_id}` is ambiguous")]
+    AmbiguousAltId {
+        alt_id: String,
 
-        /// Henry number of the person on the top of the stack, which should be the parent of `line_hn`
-        top_hn: HenryNumber,
+        alt_id_span: Span,
 
-        /// Span of the person on the top of the stack
-        top_span: Span,
+        /// The most likely reading, where each zero belongs to the digit before it
+        suggested_hn: HenryNumber,
     },
 
-    #[error("{line_name} has different henry number from their (supposed) partner")]
-    TopNotPartner {
-        line_name: String,
+    #[error("alternate henry number `{alt_id}` could be read as {hn} or as {other_hn}")]
+    AltIdEndsInZeros {
+        alt_id: String,
 
-        line_name_span: Span,
-
-        /// Henry number of the line that appears misplaced
-        line_hn: HenryNumber,
+        alt_id_span: Span,
 
-        /// Span of the henry number on the line that appears misplaced
-        line_hn_span: Span,
-
-        /// Span of the spousal index
-        spousal_index_span: Span,
+        /// How we read it: one digit per generation, padded with zeros
+        hn: HenryNumber,
 
-        /// Name of the person on the top of the stack
-        top_name: String,
+        /// The other reading, where the last generation takes the first of the zeros
+        other_hn: HenryNumber,
+    },
 
-        /// Henry number of the person on the top of the stack, which should be the parent of `line_hn`
-        top_hn: HenryNumber,
+    #[error(
+        "Child {line_name} has wrong henry number relative to their (Supposed) parent {top_name}",
+        line_name = .0.line_name,
+        top_name = .0.top_name,
+    )]
+    TopNotParent(Box<TopNotParent>),
 
-        /// Span of the person on the top of the stack
-        top_span: Span,
-    },
+    #[error(
+        "{line_name} has different henry number from their (supposed) partner",
+        line_name = .0.line_name,
+    )]
+    TopNotPartner(Box<TopNotPartner>),
 
-    #[error("Sibling {line_name} has the same henry number as {sibling_name}")]
-    SiblingWithSameHenryNumber {
-        line_name: String,
-        line_name_span: Span,
-        line_hn: HenryNumber,
-        line_hn_span: Span,
-        sibling_name: String,
-        sibling_span: Span,
-    },
+    #[error(
+        "Sibling {line_name} has the same henry number as {sibling_name}",
+        line_name = .0.line_name,
+        sibling_name = .0.sibling_name,
+    )]
+    SiblingWithSameHenryNumber(Box<SiblingWithSameHenryNumber>),
 
     #[error("name does not match, expected {expected_name} found {found_name}")]
     MismatchedName {
         expected_name: String,
+
+        /// Span of the name on the line the person was first read from
+        existing: Span,
         found_name: String,
+        found: Span,
     },
 
     #[error("{name} already has a primary henry number, {hn}")]
-    TwoPrimaryHenryNumbers { name: String, hn: HenryNumber },
+    TwoPrimaryHenryNumbers {
+        name: String,
+        hn: HenryNumber,
+        hn_span: Span,
+
+        /// Span of the henry number on the earlier primary line
+        other_span: Span,
+    },
+
+    #[error("{name} is {gender} on this line but {other_gender} on another")]
+    DifferentGender {
+        name: String,
+        gender: Gender,
+        gender_span: Span,
+        other_gender: Gender,
+        other_span: Span,
+    },
+
+    #[error(
+        "{name} is declared to have {num_kids} children with one partner but only {total} in total"
+    )]
+    MoreKidsThanTotal {
+        name: String,
+
+        /// Number of kids declared on a spouse line
+        num_kids: usize,
+        num_kids_span: Span,
+
+        /// Number of kids declared on the primary line
+        total: usize,
+        total_span: Span,
+    },
+
+    #[error("`{name}` looks like `{other_name}` but is written differently")]
+    SimilarNames {
+        name: String,
+        name_span: Span,
+        other_name: String,
+        other_span: Span,
+    },
 
     #[error("comments for {name} differ")]
     DifferentComments {
@@ -93,24 +148,355 @@ pub enum ParseErrorKind {
         other_span: Span,
     },
 
+    #[error("private comments for {name} differ")]
+    DifferentPrivateComments {
+        name: String,
+        name_span: Span,
+        comments_span: Span,
+        other_span: Span,
+    },
+
     #[error(
         "no person named {name} found with henry number {hn}, found names {}",
-        comma(.existing_names.iter()),
+        comma(.0.existing_names.iter()),
+        name = .0.name,
+        hn = .0.hn,
     )]
-    NoMatchingPerson {
+    NoMatchingPerson(Box<NoMatchingPerson>),
+
+    #[error("{name} refers to henry number {hn}, but nobody has that henry number")]
+    UnresolvedAltId {
         name: String,
+        name_span: Span,
         hn: HenryNumber,
         hn_span: Span,
-        existing_names: Vec<String>,
-        existing_name_spans: Vec<Span>,
+    },
+
+    #[error(
+        "{line_name} is indented {found} spaces but generation {} should be indented {expected}",
+        .0.line_hn.ancestry.len(),
+        line_name = .0.line_name,
+        found = .0.found,
+        expected = .0.expected,
+    )]
+    WrongIndentation(Box<WrongIndentation>),
+
+    #[error("{line_name} has no parent but is not a root ancestor")]
+    NotRootAncestor {
+        line_name: String,
+        line_name_span: Span,
+
+        /// Span of the (all zero) henry number
+        line_hn_span: Span,
+    },
+
+    #[error(
+        "Root ancestor {line_name} has the same henry number as {other_name}",
+        line_name = .0.line_name,
+        other_name = .0.other_name,
+    )]
+    DuplicateRootAncestor(Box<DuplicateRootAncestor>),
+
+    #[error("{line_name} is listed as a descendant but has no henry number of their own")]
+    NotPrimaryDescendant {
+        line_name: String,
+        line_name_span: Span,
+        spousal_index_span: Span,
+    },
+
+    #[error("Spouse {line_name} does not follow anyone with henry number {line_hn}")]
+    NoPartnerForSpouse {
+        line_name: String,
+        line_name_span: Span,
+
+        /// Henry number of the partner we expected to find
+        line_hn: HenryNumber,
+
+        /// Span of the spousal index
+        spousal_index_span: Span,
+    },
+
+    #[error("{line_name} does not follow their parent, who has henry number {parent_hn}")]
+    NoParentFound {
+        line_name: String,
+        line_name_span: Span,
+        line_hn_span: Span,
+
+        /// Henry number of the parent we expected to find
+        parent_hn: HenryNumber,
+    },
+
+    #[error("{name} is declared to have {num_kids} children but {} were found", .child_names.len())]
+    WrongNumberOfKids {
+        name: String,
+
+        /// Number of children declared on the line
+        num_kids: usize,
+
+        /// Span of the declared number of children
+        num_kids_span: Span,
+
+        /// Names of the children that were actually found
+        child_names: Vec<String>,
+
+        /// Spans of the children that were actually found
+        child_spans: Vec<Span>,
+    },
+
+    #[error("{name} is declared to have {num_spouses} spouses but {} were found", .spouse_names.len())]
+    WrongNumberOfSpouses {
+        name: String,
+
+        /// Number of spouses declared on the line
+        num_spouses: usize,
+
+        /// Span of the declared number of spouses
+        num_spouses_span: Span,
+
+        /// Names of the spouses listed beneath this person
+        spouse_names: Vec<String>,
+
+        /// Spans of the spouses listed beneath this person
+        spouse_spans: Vec<Span>,
+    },
+
+    #[error(
+        "{name} has spousal index {spousal_index} but is spouse number {expected} of {partner_name}",
+        name = .0.name,
+        spousal_index = .0.spousal_index,
+        expected = .0.expected,
+        partner_name = .0.partner_name,
+    )]
+    SpousalIndexOutOfSequence(Box<SpousalIndexOutOfSequence>),
+
+    #[error(
+        "{name} has persistent id `{id}` on this line but `{other_id}` on another",
+        name = .0.name,
+        id = .0.id,
+        other_id = .0.other_id,
+    )]
+    DifferentPersistentIds(Box<DifferentPersistentIds>),
+
+    #[error(
+        "persistent id `{id}` is used for both {other_name} and {name}",
+        id = .0.id,
+        other_name = .0.other_name,
+        name = .0.name,
+    )]
+    DuplicatePersistentId(Box<DuplicatePersistentId>),
+
+    #[error("{name} is listed as their own spouse")]
+    SelfPartnership {
+        name: String,
+        name_span: Span,
+
+        /// Span of the spouse line (where the alternate henry number refers back to the partner)
+        spouse_span: Span,
+    },
+
+    #[error("{name} is their own ancestor")]
+    AncestryCycle {
+        name: String,
+        name_span: Span,
+
+        /// The ancestors that lead from `name` back to themselves, starting with a parent
+        /// and ending with `name`
+        ancestor_names: Vec<String>,
+        ancestor_spans: Vec<Span>,
+    },
+
+    #[error("{name} is both a parent and a sibling of {child_name}")]
+    ParentAndSibling {
+        name: String,
+        name_span: Span,
+        child_name: String,
+        child_span: Span,
+    },
+
+    #[error("{name} is a child of their own partner, {partner_name}")]
+    ChildOfPartner {
+        name: String,
+        name_span: Span,
+        partner_name: String,
+        partner_span: Span,
     },
 
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+// The details of the variants with the most fields are kept in a box,
+// so that a `Result` carrying a `ParseErrorKind` stays small.
+
+#[derive(Debug)]
+pub struct TopNotParent {
+    pub line_name: String,
+
+    pub line_name_span: Span,
+
+    /// Henry number of the line that appears misplaced
+    pub line_hn: HenryNumber,
+
+    /// Span of the henry number on the line that appears misplaced
+    pub line_hn_span: Span,
+
+    /// Name of the person on the top of the stack
+    pub top_name: String,
+
+    /// Henry number of the person on the top of the stack, which should be the parent of `line_hn`
+    pub top_hn: HenryNumber,
+
+    /// Span of the person on the top of the stack
+    pub top_span: Span,
+}
+
+#[derive(Debug)]
+pub struct TopNotPartner {
+    pub line_name: String,
+
+    /// Henry number of the line that appears misplaced
+    pub line_hn: HenryNumber,
+
+    /// Span of the henry number on the line that appears misplaced
+    pub line_hn_span: Span,
+
+    /// Span of the spousal index
+    pub spousal_index_span: Span,
+
+    /// Name of the person on the top of the stack
+    pub top_name: String,
+
+    /// Henry number of the person on the top of the stack, which should be the parent of `line_hn`
+    pub top_hn: HenryNumber,
+
+    /// Span of the person on the top of the stack
+    pub top_span: Span,
+}
+
+#[derive(Debug)]
+pub struct SiblingWithSameHenryNumber {
+    pub line_name: String,
+    pub line_name_span: Span,
+    pub line_hn: HenryNumber,
+    pub line_hn_span: Span,
+
+    /// First henry number not already taken by one of the parent's children
+    /// (if the numbers haven't run out)
+    pub suggested_hn: Option<HenryNumber>,
+
+    pub sibling_name: String,
+    pub sibling_span: Span,
+}
+
+#[derive(Debug)]
+pub struct NoMatchingPerson {
+    pub name: String,
+    pub name_span: Span,
+    pub hn: HenryNumber,
+    pub hn_span: Span,
+
+    /// The people with henry number `hn`, closest name first
+    pub existing_names: Vec<String>,
+    pub existing_name_spans: Vec<Span>,
+
+    /// An existing name close enough to `name` that it may be another spelling
+    pub suggestion: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct SpousalIndexOutOfSequence {
+    pub name: String,
+    pub spousal_index: SpousalIndex,
+    pub spousal_index_span: Span,
+
+    /// Position of this spouse among those listed beneath their partner
+    pub expected: usize,
+
+    pub partner_name: String,
+    pub partner_span: Span,
+}
+
+#[derive(Debug)]
+pub struct DifferentPersistentIds {
+    pub name: String,
+    pub id: String,
+    pub id_span: Span,
+    pub other_id: String,
+    pub other_span: Span,
+}
+
+#[derive(Debug)]
+pub struct DuplicatePersistentId {
+    pub id: String,
+    pub id_span: Span,
+    pub name: String,
+    pub other_name: String,
+    pub other_span: Span,
+}
+
+#[derive(Debug)]
+pub struct WrongIndentation {
+    pub line_name: String,
+    pub line_name_span: Span,
+    pub line_hn: HenryNumber,
+    pub line_hn_span: Span,
+
+    /// Indentation expected for the generation given by the henry number
+    pub expected: usize,
+
+    /// Indentation actually found
+    pub found: usize,
+}
+
+#[derive(Debug)]
+pub struct DuplicateRootAncestor {
+    pub line_name: String,
+    pub line_name_span: Span,
+    pub line_hn: HenryNumber,
+    pub line_hn_span: Span,
+
+    /// First root ancestor henry number not already taken (if the numbers haven't run out)
+    pub suggested_hn: Option<HenryNumber>,
+
+    /// Name of the earlier root ancestor with the same henry number
+    pub other_name: String,
+
+    /// Span of the earlier root ancestor with the same henry number
+    pub other_span: Span,
+}
+
+/// How serious a diagnostic is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+
+    /// Something that is likely a mistake but does not stop us from parsing the file
+    Warning,
+}
+
+impl ParseErrorKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            // Compact alternate henry numbers are padded with zeros, so nearly all of them
+            // end in one. The reading we pick is almost always right, but only the dotted
+            // form says so for certain.
+            ParseErrorKind::AltIdEndsInZeros { .. } => Severity::Warning,
+
+            // Look-alike spellings still match each other, so they don't break the tree.
+            ParseErrorKind::SimilarNames { .. } => Severity::Warning,
+
+            // The counts are copied from the paper records as a cross-check, so a mismatch
+            // is something for the maintainers to look into rather than a broken file.
+            ParseErrorKind::WrongNumberOfKids { .. }
+            | ParseErrorKind::WrongNumberOfSpouses { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
 fn comma(v: impl Iterator<Item: Display>) -> String {
-    let mut output = format!("");
+    let mut output = String::new();
     let mut sep = "";
 
     for e in v {
@@ -123,182 +509,849 @@ fn comma(v: impl Iterator<Item: Display>) -> String {
 
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match pretty_format(self) {
-            Ok(s) => write!(f, "{s}"),
-            Err(_) => write!(
-                f,
-                "{}:{}: {}",
-                self.path.display(),
-                self.line_num,
-                self.kind
-            ),
-        }
+        write!(f, "{}", pretty_format(self))
     }
 }
 
-fn pretty_format(parse_error: &ParseError) -> anyhow::Result<String> {
-    let source = &std::fs::read_to_string(&parse_error.path)?;
-    let message = &parse_error.kind.to_string();
-    let path_str = &parse_error.path.display().to_string();
-
-    // The offset of the starting byte for each (zero-indexed) line
-    let line_offsets: Vec<usize> = std::iter::once(0)
-        .chain(
-            source
-                .char_indices()
-                .filter(|&(_, ch)| ch == '\n')
-                .map(|(offset, _)| offset + 1),
+impl std::fmt::Display for ParseErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for error in &self.errors {
+            writeln!(f, "{error}")?;
+        }
+
+        let num_errors = self
+            .errors
+            .iter()
+            .filter(|e| e.kind.severity() == Severity::Error)
+            .count();
+        write!(
+            f,
+            "could not parse `{}` due to {num_errors} error{}",
+            self.source_map.origin(),
+            if num_errors == 1 { "" } else { "s" },
         )
-        .collect();
-
-    let line_span = |line_num: usize| -> std::ops::Range<usize> {
-        let line_index = line_num - 1; // convert from 1-index to 0-index
-        let start = line_offsets[line_index];
-        let end = line_offsets
-            .get(line_index + 1)
-            .map(|i| *i - 1)
-            .unwrap_or(source.len());
-        start..end
-    };
+    }
+}
+
+/// The role an annotation plays within a diagnostic.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationKind {
+    /// The location that is actually wrong
+    Primary,
+
+    /// Related locations that help explain the problem
+    Secondary,
+
+    /// A suggestion for how to fix the problem
+    Help,
+}
+
+/// A labeled span within a diagnostic.
+#[derive(Debug, Serialize)]
+pub struct Annotation {
+    pub kind: AnnotationKind,
+    pub span: Span,
+    pub label: String,
+}
+
+/// A `ParseError` broken out into its parts, independent of how it is rendered.
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: String,
+    pub line: usize,
+    pub annotations: Vec<Annotation>,
+}
+
+impl ParseErrors {
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.errors.iter().map(|e| e.diagnostic()).collect()
+    }
+
+    /// All the edits suggested by the errors, see [`ParseErrorKind::fixes`].
+    pub fn fixes(&self) -> Vec<Edit> {
+        self.errors.iter().flat_map(|e| e.kind.fixes()).collect()
+    }
+}
+
+impl ParseErrorKind {
+    /// Edits to `genea.doc` that are likely to repair this error.
+    /// These are the same fixes suggested by the help annotations.
+    pub fn fixes(&self) -> Vec<Edit> {
+        match self {
+            ParseErrorKind::TopNotParent(payload) if payload.top_hn == payload.line_hn => payload
+                .top_hn
+                .next_sibling()
+                .map(|sibling_hn| Edit {
+                    span: payload.line_hn_span,
+                    replacement: Replacement::HenryNumber(sibling_hn),
+                })
+                .into_iter()
+                .collect(),
+            ParseErrorKind::TopNotPartner(payload) => vec![Edit {
+                span: payload.spousal_index_span,
+                replacement: Replacement::Number(0),
+            }],
+            ParseErrorKind::SiblingWithSameHenryNumber(payload) => payload
+                .suggested_hn
+                .iter()
+                .map(|suggested_hn| Edit {
+                    span: payload.line_hn_span,
+                    replacement: Replacement::HenryNumber(suggested_hn.clone()),
+                })
+                .collect(),
+            ParseErrorKind::DuplicateRootAncestor(payload) => payload
+                .suggested_hn
+                .iter()
+                .map(|suggested_hn| Edit {
+                    span: payload.line_hn_span,
+                    replacement: Replacement::HenryNumber(suggested_hn.clone()),
+                })
+                .collect(),
+            ParseErrorKind::SpousalIndexOutOfSequence(payload) => vec![Edit {
+                span: payload.spousal_index_span,
+                replacement: Replacement::Number(payload.expected),
+            }],
+            ParseErrorKind::AmbiguousAltId {
+                alt_id_span,
+                suggested_hn,
+                ..
+            } => vec![Edit {
+                span: *alt_id_span,
+                replacement: Replacement::AltId(suggested_hn.clone()),
+            }],
+            _ => vec![],
+        }
+    }
+}
 
-    let span = |span: Span| -> std::ops::Range<usize> {
-        let line_span = line_span(span.line_num);
-        if let Some((start, end)) = span.chars {
-            line_span.start + start..line_span.start + end
-        } else {
-            line_span
+impl ParseError {
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            severity: self.kind.severity(),
+            message: self.kind.to_string(),
+            file: self.source_map.origin().to_string(),
+            line: self.line_num,
+            annotations: annotations(self),
         }
-    };
+    }
+}
 
-    let annotation1;
-    let annotation2;
-    let annotation3;
-    let annotations: Vec<String>;
-    let mut snippet = Snippet::source(source).origin(path_str).fold(true);
+fn annotations(parse_error: &ParseError) -> Vec<Annotation> {
+    let mut annotations = vec![];
+    let mut annotate = |kind: AnnotationKind, span: Span, label: String| {
+        annotations.push(Annotation { kind, span, label })
+    };
 
     match &parse_error.kind {
-        ParseErrorKind::TopNotParent {
+        ParseErrorKind::TopNotParent(payload) => {
+            let TopNotParent {
+                line_name,
+                line_name_span,
+                line_hn,
+                line_hn_span,
+                top_name,
+                top_hn,
+                top_span,
+            } = &**payload;
+
+            annotate(
+                AnnotationKind::Primary,
+                *line_name_span,
+                format!("{line_name} has incorrect henry number to be a child of {top_name}"),
+            );
+
+            if let Some(sibling_hn) = top_hn.next_sibling().filter(|_| top_hn == line_hn) {
+                annotate(
+                    AnnotationKind::Help,
+                    *line_hn_span,
+                    format!("Most likely cause is that this henry number should be {sibling_hn}"),
+                );
+            }
+
+            annotate(
+                AnnotationKind::Secondary,
+                *top_span,
+                format!("Their (supposed) parent {top_name} has henry number {top_hn}"),
+            );
+        }
+        ParseErrorKind::TopNotPartner(payload) => {
+            let TopNotPartner {
+                line_name,
+                line_hn,
+                line_hn_span,
+                spousal_index_span,
+                top_name,
+                top_hn,
+                top_span,
+            } = &**payload;
+
+            annotate(
+                AnnotationKind::Primary,
+                *line_hn_span,
+                format!("{line_name} has henry number {line_hn} which differs from their (supposed) partner's henry number"),
+            );
+
+            annotate(
+                AnnotationKind::Secondary,
+                *top_span,
+                format!("Partner {top_name} has henry number {top_hn}"),
+            );
+
+            annotate(
+                AnnotationKind::Help,
+                *spousal_index_span,
+                format!("Most likely fix is to change spousal index of {line_name} to 0"),
+            );
+        }
+        ParseErrorKind::SiblingWithSameHenryNumber(payload) => {
+            let SiblingWithSameHenryNumber {
+                line_name,
+                line_name_span,
+                line_hn,
+                line_hn_span,
+                suggested_hn,
+                sibling_name,
+                sibling_span,
+            } = &**payload;
+
+            annotate(
+                AnnotationKind::Primary,
+                *line_name_span,
+                format!("{line_name} has the same henry number as their sibling"),
+            );
+
+            annotate(
+                AnnotationKind::Secondary,
+                *sibling_span,
+                format!("Sibling {sibling_name} also has henry number {line_hn}"),
+            );
+
+            if let Some(suggested_hn) = suggested_hn {
+                annotate(
+                    AnnotationKind::Help,
+                    *line_hn_span,
+                    format!("Most likely fix is to change henry number here to {suggested_hn}"),
+                );
+            }
+        }
+        ParseErrorKind::NoMatchingPerson(payload) => {
+            let NoMatchingPerson {
+                name,
+                name_span,
+                hn,
+                hn_span,
+                existing_names,
+                existing_name_spans,
+                suggestion,
+            } = &**payload;
+
+            if let Some(suggestion) = suggestion {
+                annotate(
+                    AnnotationKind::Help,
+                    *name_span,
+                    format!("Did you mean {suggestion}? If both spellings are used, write `{name}|{suggestion}`"),
+                );
+            }
+
+            annotate(
+                AnnotationKind::Primary,
+                *hn_span,
+                format!("{name} must match somebody with henry number {hn}"),
+            );
+
+            for (existing_name, existing_name_span) in
+                existing_names.iter().zip(existing_name_spans)
+            {
+                annotate(
+                    AnnotationKind::Secondary,
+                    *existing_name_span,
+                    format!("{existing_name} declared here"),
+                );
+            }
+        }
+        ParseErrorKind::UnresolvedAltId {
+            name,
+            name_span,
+            hn,
+            hn_span,
+        } => {
+            annotate(
+                AnnotationKind::Primary,
+                *hn_span,
+                format!("no line has henry number {hn}"),
+            );
+
+            annotate(
+                AnnotationKind::Help,
+                *name_span,
+                format!("Either this alternate henry number is wrong or the line for {name} under {hn} is missing"),
+            );
+        }
+        ParseErrorKind::MismatchedName {
+            expected_name,
+            existing,
+            found_name: _,
+            found,
+        } => {
+            annotate(
+                AnnotationKind::Secondary,
+                *existing,
+                format!("{expected_name} here"),
+            );
+
+            annotate(
+                AnnotationKind::Primary,
+                *found,
+                "but named differently here".to_string(),
+            );
+        }
+        ParseErrorKind::TwoPrimaryHenryNumbers {
+            name,
+            hn,
+            hn_span,
+            other_span,
+        } => {
+            annotate(
+                AnnotationKind::Secondary,
+                *other_span,
+                format!("{name} already has primary henry number {hn} here"),
+            );
+
+            annotate(
+                AnnotationKind::Primary,
+                *hn_span,
+                "second primary henry number".to_string(),
+            );
+
+            annotate(
+                AnnotationKind::Help,
+                *hn_span,
+                "One of these lines should be a spouse line (with a non-zero spousal index)"
+                    .to_string(),
+            );
+        }
+        ParseErrorKind::DifferentGender {
+            name: _,
+            gender,
+            gender_span,
+            other_gender,
+            other_span,
+        } => {
+            annotate(
+                AnnotationKind::Secondary,
+                *other_span,
+                format!("{other_gender} here"),
+            );
+
+            annotate(
+                AnnotationKind::Primary,
+                *gender_span,
+                format!("{gender} here"),
+            );
+        }
+        ParseErrorKind::MoreKidsThanTotal {
+            name: _,
+            num_kids,
+            num_kids_span,
+            total,
+            total_span,
+        } => {
+            annotate(
+                AnnotationKind::Secondary,
+                *total_span,
+                format!("{total} children in total declared here"),
+            );
+
+            annotate(
+                AnnotationKind::Primary,
+                *num_kids_span,
+                format!("{num_kids} children with this partner declared here"),
+            );
+        }
+        ParseErrorKind::SimilarNames {
+            name: _,
+            name_span,
+            other_name,
+            other_span,
+        } => {
+            annotate(
+                AnnotationKind::Secondary,
+                *other_span,
+                format!("{other_name} written this way here"),
+            );
+
+            annotate(
+                AnnotationKind::Primary,
+                *name_span,
+                "differs only in accents, apostrophes or spacing".to_string(),
+            );
+        }
+        ParseErrorKind::DifferentComments {
+            name,
+            name_span,
+            comments_span,
+            other_span,
+        }
+        | ParseErrorKind::DifferentPrivateComments {
+            name,
+            name_span,
+            comments_span,
+            other_span,
+        } => {
+            annotate(
+                AnnotationKind::Secondary,
+                *other_span,
+                "Other comments found on this line".to_string(),
+            );
+
+            annotate(
+                AnnotationKind::Secondary,
+                *comments_span,
+                "Comment that is different".to_string(),
+            );
+
+            annotate(
+                AnnotationKind::Primary,
+                *name_span,
+                format!("{name} has different comments on this line"),
+            );
+        }
+        ParseErrorKind::MalformedField {
+            field: _,
+            span,
+            expected,
+            found,
+        } => {
+            annotate(
+                AnnotationKind::Primary,
+                *span,
+                format!("found {found} where {expected} was expected"),
+            );
+        }
+        ParseErrorKind::AmbiguousAltId {
+            alt_id: _,
+            alt_id_span,
+            suggested_hn,
+        } => {
+            annotate(
+                AnnotationKind::Primary,
+                *alt_id_span,
+                "a zero before the last generation could belong to the digit before it".to_string(),
+            );
+
+            annotate(
+                AnnotationKind::Help,
+                *alt_id_span,
+                format!(
+                    "If this is {suggested_hn}, write it as `{}`",
+                    suggested_hn.to_alt_string()
+                ),
+            );
+        }
+        ParseErrorKind::AltIdEndsInZeros {
+            alt_id: _,
+            alt_id_span,
+            hn,
+            other_hn,
+        } => {
+            annotate(
+                AnnotationKind::Primary,
+                *alt_id_span,
+                format!("read as {hn}, but the last zero could belong to the last generation"),
+            );
+
+            annotate(
+                AnnotationKind::Help,
+                *alt_id_span,
+                format!(
+                    "Write it as `{}` for {hn}, or as `{}` for {other_hn}",
+                    hn.to_dotted_alt_string(),
+                    other_hn.to_dotted_alt_string(),
+                ),
+            );
+        }
+        ParseErrorKind::WrongIndentation(payload) => {
+            let WrongIndentation {
+                line_name: _,
+                line_name_span,
+                line_hn,
+                line_hn_span,
+                expected,
+                found,
+            } = &**payload;
+
+            annotate(
+                AnnotationKind::Primary,
+                *line_name_span,
+                format!("indented {found} spaces, expected {expected}"),
+            );
+
+            annotate(
+                AnnotationKind::Help,
+                *line_hn_span,
+                format!("Either the indentation is wrong or the henry number {line_hn} is"),
+            );
+        }
+        ParseErrorKind::NotRootAncestor {
             line_name,
             line_name_span,
-            line_hn,
             line_hn_span,
-            top_name,
-            top_hn,
-            top_span,
         } => {
-            annotation1 =
-                format!("{line_name} has incorrect henry number to be a child of {top_name}",);
-            snippet =
-                snippet.annotation(Level::Error.span(span(*line_name_span)).label(&annotation1));
-
-            if top_hn == line_hn {
-                annotation2 = format!(
-                    "Most likely cause is that this henry number should be {sibling_hn}",
-                    sibling_hn = top_hn.next_sibling()
+            annotate(
+                AnnotationKind::Primary,
+                *line_name_span,
+                format!("{line_name} has no parent but is not a root ancestor"),
+            );
+
+            annotate(
+                AnnotationKind::Help,
+                *line_hn_span,
+                "Henry numbers must start with a non-zero number".to_string(),
+            );
+        }
+        ParseErrorKind::DuplicateRootAncestor(payload) => {
+            let DuplicateRootAncestor {
+                line_name,
+                line_name_span,
+                line_hn,
+                line_hn_span,
+                suggested_hn,
+                other_name,
+                other_span,
+            } = &**payload;
+
+            annotate(
+                AnnotationKind::Primary,
+                *line_name_span,
+                format!("{line_name} has the same henry number as another root ancestor"),
+            );
+
+            annotate(
+                AnnotationKind::Secondary,
+                *other_span,
+                format!("Root ancestor {other_name} also has henry number {line_hn}"),
+            );
+
+            if let Some(suggested_hn) = suggested_hn {
+                annotate(
+                    AnnotationKind::Help,
+                    *line_hn_span,
+                    format!("Most likely fix is to change henry number here to {suggested_hn}"),
                 );
-                snippet =
-                    snippet.annotation(Level::Help.span(span(*line_hn_span)).label(&annotation2));
             }
+        }
+        ParseErrorKind::NotPrimaryDescendant {
+            line_name,
+            line_name_span,
+            spousal_index_span,
+        } => {
+            annotate(
+                AnnotationKind::Primary,
+                *line_name_span,
+                format!("{line_name} has no henry number of their own"),
+            );
 
-            annotation3 = format!("Their (supposed) parent {top_name} has henry number {top_hn}");
-            snippet = snippet.annotation(Level::Info.span(span(*top_span)).label(&annotation3));
+            annotate(
+                AnnotationKind::Help,
+                *spousal_index_span,
+                "Descendants must have a spousal index of 0".to_string(),
+            );
         }
-        ParseErrorKind::TopNotPartner {
+        ParseErrorKind::NoPartnerForSpouse {
             line_name,
-            line_name_span: _,
+            line_name_span,
             line_hn,
-            line_hn_span,
             spousal_index_span,
-            top_name,
-            top_hn,
-            top_span,
         } => {
-            annotation1 =
-                format!("{line_name} has henry number {line_hn} which differs from their (supposed) partner's henry number",);
-            snippet =
-                snippet.annotation(Level::Error.span(span(*line_hn_span)).label(&annotation1));
-
-            annotation2 = format!("Partner {top_name} has henry number {top_hn}");
-            snippet = snippet.annotation(Level::Info.span(span(*top_span)).label(&annotation2));
+            annotate(
+                AnnotationKind::Primary,
+                *line_name_span,
+                format!("{line_name} has no partner with henry number {line_hn} before them"),
+            );
 
-            annotation3 = format!("Most likely fix is to change spousal index of {line_name} to 0");
-            snippet = snippet.annotation(
-                Level::Help
-                    .span(span(*spousal_index_span))
-                    .label(&annotation3),
+            annotate(
+                AnnotationKind::Help,
+                *spousal_index_span,
+                format!("Spouses must come after their partner; if {line_name} is a descendant, change the spousal index to 0"),
             );
         }
-        ParseErrorKind::SiblingWithSameHenryNumber {
+        ParseErrorKind::NoParentFound {
             line_name,
             line_name_span,
-            line_hn,
             line_hn_span,
-            sibling_name,
-            sibling_span,
+            parent_hn,
+        } => {
+            annotate(
+                AnnotationKind::Primary,
+                *line_name_span,
+                format!("{line_name} does not come after their parent"),
+            );
+
+            annotate(
+                AnnotationKind::Help,
+                *line_hn_span,
+                format!("Either this henry number is wrong or the line for {parent_hn} is missing"),
+            );
+        }
+        ParseErrorKind::WrongNumberOfKids {
+            name: _,
+            num_kids,
+            num_kids_span,
+            child_names,
+            child_spans,
+        } => {
+            annotate(
+                AnnotationKind::Primary,
+                *num_kids_span,
+                format!("{num_kids} children declared here"),
+            );
+
+            for (child_name, child_span) in child_names.iter().zip(child_spans) {
+                annotate(
+                    AnnotationKind::Secondary,
+                    *child_span,
+                    format!("child {child_name} found here"),
+                );
+            }
+
+            annotate(
+                AnnotationKind::Help,
+                *num_kids_span,
+                "If this matches the paper record, a child may be missing from this file"
+                    .to_string(),
+            );
+        }
+        ParseErrorKind::WrongNumberOfSpouses {
+            name: _,
+            num_spouses,
+            num_spouses_span,
+            spouse_names,
+            spouse_spans,
+        } => {
+            annotate(
+                AnnotationKind::Primary,
+                *num_spouses_span,
+                format!("{num_spouses} spouses declared here"),
+            );
+
+            for (spouse_name, spouse_span) in spouse_names.iter().zip(spouse_spans) {
+                annotate(
+                    AnnotationKind::Secondary,
+                    *spouse_span,
+                    format!("spouse {spouse_name} found here"),
+                );
+            }
+
+            annotate(
+                AnnotationKind::Help,
+                *num_spouses_span,
+                "If this matches the paper record, a spouse may be missing from this file"
+                    .to_string(),
+            );
+        }
+        ParseErrorKind::SpousalIndexOutOfSequence(payload) => {
+            let SpousalIndexOutOfSequence {
+                name: _,
+                spousal_index: _,
+                spousal_index_span,
+                expected,
+                partner_name,
+                partner_span,
+            } = &**payload;
+
+            annotate(
+                AnnotationKind::Secondary,
+                *partner_span,
+                format!("spouses of {partner_name} are numbered from here"),
+            );
+
+            annotate(
+                AnnotationKind::Primary,
+                *spousal_index_span,
+                format!("expected {expected}"),
+            );
+
+            annotate(
+                AnnotationKind::Help,
+                *spousal_index_span,
+                "Spouses are numbered 1, 2, 3... in the order they are listed".to_string(),
+            );
+        }
+        ParseErrorKind::DifferentPersistentIds(payload) => {
+            let DifferentPersistentIds {
+                name: _,
+                id,
+                id_span,
+                other_id,
+                other_span,
+            } = &**payload;
+            annotate(
+                AnnotationKind::Secondary,
+                *other_span,
+                format!("`{other_id}` given here"),
+            );
+
+            annotate(
+                AnnotationKind::Primary,
+                *id_span,
+                format!("`{id}` given here"),
+            );
+        }
+        ParseErrorKind::DuplicatePersistentId(payload) => {
+            let DuplicatePersistentId {
+                id: _,
+                id_span,
+                name,
+                other_name,
+                other_span,
+            } = &**payload;
+            annotate(
+                AnnotationKind::Secondary,
+                *other_span,
+                format!("used for {other_name} here"),
+            );
+
+            annotate(
+                AnnotationKind::Primary,
+                *id_span,
+                format!("used again for {name}"),
+            );
+
+            annotate(
+                AnnotationKind::Help,
+                *id_span,
+                "Remove this id and run `assign-ids` to generate a new one".to_string(),
+            );
+        }
+        ParseErrorKind::SelfPartnership {
+            name,
+            name_span,
+            spouse_span,
         } => {
-            annotation1 = format!("{line_name} has the same henry number as their sibling",);
-            snippet =
-                snippet.annotation(Level::Error.span(span(*line_name_span)).label(&annotation1));
+            annotate(
+                AnnotationKind::Secondary,
+                *name_span,
+                format!("{name} declared here"),
+            );
 
-            annotation2 = format!("Sibling {sibling_name} also has henry number {line_hn}");
-            snippet = snippet.annotation(Level::Info.span(span(*sibling_span)).label(&annotation2));
+            annotate(
+                AnnotationKind::Primary,
+                *spouse_span,
+                format!("{name} is listed again as a spouse of themselves"),
+            );
 
-            annotation3 = format!("Most likely fix is to change henry number here");
-            snippet = snippet.annotation(Level::Help.span(span(*line_hn_span)).label(&annotation3));
+            annotate(
+                AnnotationKind::Help,
+                *spouse_span,
+                "Check the alternate henry number on the spouse line".to_string(),
+            );
         }
-        ParseErrorKind::NoMatchingPerson {
+        ParseErrorKind::AncestryCycle {
             name,
-            hn,
-            hn_span,
-            existing_names,
-            existing_name_spans,
+            name_span,
+            ancestor_names,
+            ancestor_spans,
         } => {
-            annotation1 = format!("{name} must match somebody with henry number {hn}",);
-            snippet = snippet.annotation(Level::Error.span(span(*hn_span)).label(&annotation1));
+            annotate(
+                AnnotationKind::Primary,
+                *name_span,
+                format!("{name} declared here"),
+            );
 
-            annotations = existing_names
-                .iter()
-                .map(|n| format!("{n} declared here"))
-                .collect();
+            for (ancestor_name, ancestor_span) in ancestor_names.iter().zip(ancestor_spans) {
+                if ancestor_span == name_span {
+                    continue;
+                }
 
-            for (existing_name_annotation, existing_name_span) in
-                annotations.iter().zip(existing_name_spans)
-            {
-                snippet = snippet.annotation(
-                    Level::Info
-                        .span(span(*existing_name_span))
-                        .label(&existing_name_annotation),
+                annotate(
+                    AnnotationKind::Secondary,
+                    *ancestor_span,
+                    format!("ancestor {ancestor_name} declared here"),
                 );
             }
         }
-        ParseErrorKind::DifferentComments {
+        ParseErrorKind::ParentAndSibling {
             name,
             name_span,
-            comments_span,
-            other_span,
+            child_name,
+            child_span,
         } => {
-            annotation2 = format!("Other comments found on this line");
-            snippet = snippet.annotation(Level::Info.span(span(*other_span)).label(&annotation2));
+            annotate(
+                AnnotationKind::Primary,
+                *name_span,
+                format!("{name} declared here"),
+            );
 
-            annotation3 = format!("Comment that is different");
-            snippet =
-                snippet.annotation(Level::Info.span(span(*comments_span)).label(&annotation3));
+            annotate(
+                AnnotationKind::Secondary,
+                *child_span,
+                format!("{child_name} has the same parents as {name}"),
+            );
+        }
+        ParseErrorKind::ChildOfPartner {
+            name,
+            name_span,
+            partner_name,
+            partner_span,
+        } => {
+            annotate(
+                AnnotationKind::Primary,
+                *name_span,
+                format!("{name} declared here"),
+            );
 
-            annotation1 = format!("{name} has different comments on this line");
-            snippet = snippet.annotation(Level::Error.span(span(*name_span)).label(&annotation1));
+            annotate(
+                AnnotationKind::Secondary,
+                *partner_span,
+                format!("{partner_name} is both parent and partner of {name}"),
+            );
         }
         _ => {
-            snippet = snippet.annotation(
-                Level::Error
-                    .span(line_span(parse_error.line_num))
-                    .label("here"),
+            annotate(
+                AnnotationKind::Primary,
+                Span {
+                    line_num: parse_error.line_num,
+                    chars: None,
+                },
+                "here".to_string(),
             );
         }
     }
 
-    let message = Level::Error.title(message).snippet(snippet);
+    annotations
+}
+
+fn pretty_format(parse_error: &ParseError) -> String {
+    let source_map = &parse_error.source_map;
+    let diagnostic = parse_error.diagnostic();
+
+    let level = match diagnostic.severity {
+        Severity::Error => Level::Error,
+        Severity::Warning => Level::Warning,
+    };
+
+    let mut snippet = Snippet::source(source_map.text())
+        .origin(&diagnostic.file)
+        .fold(true);
+    for annotation in &diagnostic.annotations {
+        let level = match annotation.kind {
+            AnnotationKind::Primary => level,
+            AnnotationKind::Secondary => Level::Info,
+            AnnotationKind::Help => Level::Help,
+        };
+        snippet = snippet.annotation(
+            level
+                .span(source_map.span_range(annotation.span))
+                .label(&annotation.label),
+        );
+    }
+
+    let message = level.title(&diagnostic.message).snippet(snippet);
 
     let result = Renderer::plain().render(message).to_string();
 
-    Ok(result)
+    result
 }
@@ -0,0 +1,330 @@
+//! Applying the repairs suggested by parse errors back to the text of a `genea.doc` file,
+//! and other automated edits such as assigning persistent ids and formatting.
+
+use std::{collections::BTreeSet, path::Path};
+
+use super::{parser, HenryNumber, ParseErrors, ParseOptions, Span};
+
+#[cfg(test)]
+mod tests;
+
+/// A suggested replacement for a single field of a line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub span: Span,
+    pub replacement: Replacement,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Replacement {
+    /// Replace the henry number columns, keeping the same number of columns
+    HenryNumber(HenryNumber),
+
+    /// Replace a single number, such as the spousal index
+    Number(usize),
+
+    /// Replace an alternate henry number (which may need to grow into the dotted form)
+    AltId(HenryNumber),
+
+    /// Insert a persistent id (the span is expected to be empty)
+    PersonId(String),
+}
+
+impl Replacement {
+    /// Text to replace `original` with, laid out to take the same width
+    /// so that the columns of the file stay aligned.
+    fn layout(&self, original: &str) -> String {
+        match self {
+            Replacement::HenryNumber(hn) => {
+                let mut values = hn.ancestry.iter().copied();
+                let mut output = String::new();
+                let mut chars = original.char_indices().peekable();
+
+                // Replace each existing column with the new value (or a trailing zero),
+                // right-aligned within the width of the old one.
+                while let Some((start, ch)) = chars.next() {
+                    if !ch.is_ascii_digit() {
+                        output.push(ch);
+                        continue;
+                    }
+
+                    let mut end = start + 1;
+                    while let Some((_, ch)) = chars.next_if(|(_, ch)| ch.is_ascii_digit()) {
+                        end += ch.len_utf8();
+                    }
+
+                    let value = values.next().unwrap_or(0);
+                    let width = end - start;
+                    output.push_str(&format!("{value:>width$}"));
+                }
+
+                // The new henry number may be deeper than the number of columns available.
+                let extra: Vec<String> = values.map(|v| v.to_string()).collect();
+                if !extra.is_empty() {
+                    let trailing = output.len() - output.trim_end().len();
+                    output.truncate(output.len() - trailing);
+                    output.push(' ');
+                    output.push_str(&extra.join(" "));
+                    output.push_str(&" ".repeat(trailing.max(1)));
+                }
+
+                // If we grew, give back leading whitespace to keep the remaining columns in place.
+                let excess = output.len().saturating_sub(original.len());
+                let leading = output.len() - output.trim_start().len();
+                output.drain(..excess.min(leading));
+
+                output
+            }
+
+            Replacement::Number(n) => {
+                let width = original.len();
+                format!("{n:>width$}")
+            }
+
+            Replacement::AltId(hn) => {
+                let width = original.len();
+                format!("{:<width$}", hn.to_alt_string())
+            }
+
+            Replacement::PersonId(id) => format!("#{id} {original}"),
+        }
+    }
+}
+
+/// The outcome of [`apply_edits`].
+#[derive(Debug)]
+pub struct EditedText {
+    pub text: String,
+
+    /// Number of edits that were made to the text
+    pub num_applied: usize,
+
+    /// Edits that were dropped because they overlap an earlier edit on the same line
+    pub skipped: Vec<Edit>,
+}
+
+/// Applies `edits` to `text`. Edits that overlap an earlier edit are dropped.
+pub fn apply_edits(text: &str, edits: &[Edit]) -> EditedText {
+    let mut lines: Vec<String> = text.split_inclusive('\n').map(|s| s.to_string()).collect();
+
+    let mut edits: Vec<&Edit> = edits.iter().filter(|e| e.span.chars.is_some()).collect();
+    edits.sort_by_key(|e| e.span);
+    edits.dedup();
+
+    // Apply the edits to each line from right to left so that earlier offsets remain valid.
+    let mut last: Option<Span> = None;
+    let mut accepted = vec![];
+    let mut skipped = vec![];
+    for edit in edits {
+        if let Some(last) = last {
+            if last.line_num == edit.span.line_num
+                && last.chars.unwrap().1 > edit.span.chars.unwrap().0
+            {
+                skipped.push(edit.clone());
+                continue;
+            }
+        }
+        last = Some(edit.span);
+        accepted.push(edit);
+    }
+
+    let mut num_applied = 0;
+    for edit in accepted.into_iter().rev() {
+        let Some(line) = lines.get_mut(edit.span.line_num - 1) else {
+            continue;
+        };
+        let (start, end) = edit.span.chars.unwrap();
+        let Some(original) = line.get(start..end) else {
+            continue;
+        };
+        let replacement = edit.replacement.layout(original);
+        line.replace_range(start..end, &replacement);
+        num_applied += 1;
+    }
+
+    EditedText {
+        text: lines.concat(),
+        num_applied,
+        skipped,
+    }
+}
+
+/// A unified diff of `old` and `new`, without context lines.
+pub fn diff(path: &Path, old: &str, new: &str) -> String {
+    let path = path.display().to_string();
+    similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(0)
+        .header(&path, &path)
+        .to_string()
+}
+
+/// Applies the fixes suggested by the parse errors in the file at `path`,
+/// rewriting it in place. With `dry_run`, the changes are printed as a diff instead.
+pub fn fix_genea_doc(path: &Path, dry_run: bool, options: ParseOptions) -> anyhow::Result<()> {
+    let text = std::fs::read_to_string(path)?;
+
+    let edits = match parser::parse_text(path.display(), &text, options) {
+        Ok(_) => vec![],
+        Err(e) => match e.downcast_ref::<ParseErrors>() {
+            Some(errors) => errors.fixes(),
+            None => return Err(e),
+        },
+    };
+
+    if edits.is_empty() {
+        eprintln!("no fixes to apply to `{}`", path.display());
+        return Ok(());
+    }
+
+    let fixed = apply_edits(&text, &edits);
+
+    for edit in &fixed.skipped {
+        eprintln!(
+            "{}:{}: skipped a fix that overlaps another fix on the same line; \
+             run `check --fix` again once the file has been fixed",
+            path.display(),
+            edit.span.line_num,
+        );
+    }
+
+    if write_or_diff(path, &text, &fixed.text, dry_run)? {
+        eprintln!(
+            "applied {} fixes to `{}`",
+            fixed.num_applied,
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Gives a persistent id to everybody in the file at `path` who does not have one yet,
+/// inserting it as `#id` before the first occurrence of their name.
+/// With `dry_run`, the changes are printed as a diff instead.
+pub fn assign_person_ids(path: &Path, dry_run: bool) -> anyhow::Result<()> {
+    let text = std::fs::read_to_string(path)?;
+    let genea = parser::parse_text(path.display(), &text, ParseOptions::default())?;
+
+    let mut taken: BTreeSet<String> = genea
+        .people()
+        .filter_map(|p| genea[p].persistent_id.clone())
+        .collect();
+
+    let mut edits = vec![];
+    for person in genea.people() {
+        let person_data = &genea[person];
+        if person_data.persistent_id.is_some() {
+            continue;
+        }
+
+        let Some((start, _)) = person_data.span.chars else {
+            continue;
+        };
+
+        let id = generate_person_id(
+            &format!("{}/{}", person_data.name, genea.id(person)),
+            &taken,
+        );
+        taken.insert(id.clone());
+        edits.push(Edit {
+            span: Span {
+                line_num: person_data.span.line_num,
+                chars: Some((start, start)),
+            },
+            replacement: Replacement::PersonId(id),
+        });
+    }
+
+    if edits.is_empty() {
+        eprintln!(
+            "everybody in `{}` already has a persistent id",
+            path.display()
+        );
+        return Ok(());
+    }
+
+    // Each id goes on a different line, so none of the edits overlap.
+    let edited = apply_edits(&text, &edits);
+    if write_or_diff(path, &text, &edited.text, dry_run)? {
+        eprintln!(
+            "assigned {} persistent ids in `{}`",
+            edited.num_applied,
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Lays out the person lines of the file at `path` in aligned columns (see
+/// [`parser::format_text`]), rewriting it in place. With `check`, the file is left
+/// alone and the changes that formatting would make are printed as a diff instead,
+/// failing if there are any.
+pub fn format_genea_doc(path: &Path, check: bool) -> anyhow::Result<()> {
+    let text = std::fs::read_to_string(path)?;
+    let formatted = parser::format_text(path.display(), &text)?;
+
+    if formatted == text {
+        return Ok(());
+    }
+
+    if check {
+        print!("{}", diff(path, &text, &formatted));
+        anyhow::bail!(
+            "`{}` is not formatted; run `family-tree fmt` to format it",
+            path.display()
+        );
+    }
+
+    std::fs::write(path, &formatted)?;
+    eprintln!("formatted `{}`", path.display());
+    Ok(())
+}
+
+/// A short id derived from `seed`, so that running `assign-ids` twice on the same
+/// file gives the same results, which is not already in `taken`.
+/// The hash is spelled out here because std's hashers may change between releases.
+fn generate_person_id(seed: &str, taken: &BTreeSet<String>) -> String {
+    const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+    const ALPHANUMERICS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+    (0_u64..)
+        .map(|attempt| {
+            let mut hash = fnv1a(seed.bytes().chain(attempt.to_le_bytes()));
+
+            // Ids start with a letter so that they can't be confused with henry numbers.
+            let mut id = String::new();
+            id.push(LETTERS[(hash % 26) as usize] as char);
+            hash /= 26;
+            for _ in 0..5 {
+                id.push(ALPHANUMERICS[(hash % 36) as usize] as char);
+                hash /= 36;
+            }
+            id
+        })
+        .find(|id| !taken.contains(id))
+        .expect("there are far more ids than people")
+}
+
+/// The 64-bit FNV-1a hash of `bytes`
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    bytes.into_iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// Writes `edited`, an edited copy of `text`, to `path`, or prints the changes as a diff
+/// with `dry_run`. Returns true if the file was written.
+fn write_or_diff(path: &Path, text: &str, edited: &str, dry_run: bool) -> anyhow::Result<bool> {
+    if dry_run {
+        print!("{}", diff(path, text, edited));
+        Ok(false)
+    } else {
+        std::fs::write(path, edited)?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,109 @@
+//! The repairs that `check --fix` makes to small `genea.doc` files.
+
+use std::{collections::BTreeSet, path::Path};
+
+use super::{apply_edits, diff, fnv1a, generate_person_id, Edit, EditedText, Replacement};
+use crate::genea::{parser::parse_text, ParseErrors, ParseOptions, Span};
+
+/// The result of applying the fixes suggested by the errors in `text`.
+fn fix(text: &str) -> EditedText {
+    let errors: ParseErrors = match parse_text("genea.doc", text, ParseOptions::default()) {
+        Ok(_) => panic!(
+            "expected errors in:
+{text}"
+        ),
+        Err(e) => e.downcast().unwrap(),
+    };
+    apply_edits(text, &errors.fixes())
+}
+
+#[test]
+fn sibling_with_same_henry_number_is_renumbered() {
+    let fixed = fix(" 1 0 M 2 0 0 Sarek\n 1 1 M 0 0 0 Spock\n 1 1 M 0 0 0 Sybok\n");
+    assert_eq!(
+        fixed.text,
+        " 1 0 M 2 0 0 Sarek\n 1 1 M 0 0 0 Spock\n 1 2 M 0 0 0 Sybok\n"
+    );
+    assert_eq!(fixed.num_applied, 1);
+    assert!(fixed.skipped.is_empty());
+}
+
+#[test]
+fn partner_with_a_different_henry_number_gets_spousal_index_zero() {
+    let fixed = fix(" 1 0 M 0 1 0 Sarek\n 1 1 F 0 0 1 Amanda\n");
+    assert_eq!(fixed.text, " 1 0 M 0 1 0 Sarek\n 1 1 F 0 0 0 Amanda\n");
+}
+
+#[test]
+fn wider_henry_number_keeps_the_following_columns_in_place() {
+    let mut text = String::from(" 1 0 M 10 0 0 Sarek\n");
+    for i in 1..=9 {
+        text.push_str(&format!(" 1 {i} M  0 0 0 Child {i}\n"));
+    }
+    text.push_str(" 1 9 M  0 0 0 Child 10\n");
+
+    let fixed = fix(&text);
+    let last = fixed.text.lines().last().unwrap();
+    assert_eq!(last, "1 10 M  0 0 0 Child 10");
+    assert_eq!(last.find('M'), text.lines().last().unwrap().find('M'));
+}
+
+#[test]
+fn overlapping_edits_are_skipped_and_not_counted() {
+    let span = |start, end| Span {
+        line_num: 1,
+        chars: Some((start, end)),
+    };
+    let edits = [
+        Edit {
+            span: span(1, 2),
+            replacement: Replacement::Number(2),
+        },
+        Edit {
+            span: span(1, 4),
+            replacement: Replacement::Number(3),
+        },
+        Edit {
+            span: span(11, 12),
+            replacement: Replacement::Number(0),
+        },
+    ];
+
+    let edited = apply_edits(" 1 0 F 0 0 1 Amanda\n", &edits);
+    assert_eq!(edited.text, " 2 0 F 0 0 0 Amanda\n");
+    assert_eq!(edited.num_applied, 2);
+    assert_eq!(edited.skipped, [edits[1].clone()]);
+}
+
+#[test]
+fn dry_run_diff_shows_only_changed_lines() {
+    let old = " 1 0 M 2 0 0 Sarek\n 1 1 M 0 0 0 Spock\n 1 1 M 0 0 0 Sybok\n";
+    let new = fix(old).text;
+    assert_eq!(
+        diff(Path::new("genea.doc"), old, &new),
+        "--- genea.doc\n+++ genea.doc\n@@ -3 +3 @@\n- 1 1 M 0 0 0 Sybok\n+ 1 2 M 0 0 0 Sybok\n"
+    );
+}
+
+#[test]
+fn fnv1a_matches_the_reference_values() {
+    assert_eq!(fnv1a(*b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(fnv1a(*b"a"), 0xaf63_dc4c_8601_ec8c);
+    assert_eq!(fnv1a(*b"foobar"), 0x8594_4171_f739_67e8);
+}
+
+#[test]
+fn person_ids_are_stable_and_unique() {
+    let id = generate_person_id("Spock/1-1-1", &BTreeSet::new());
+    assert_eq!(id.len(), 6);
+    assert!(id.starts_with(|c: char| c.is_ascii_lowercase()));
+
+    // The same seed gives the same id on every run and every release of Rust, since the ids
+    // end up in genea.doc and in links.
+    assert_eq!(id, "xx8kaq");
+
+    let taken = BTreeSet::from([id.clone()]);
+    let other = generate_person_id("Spock/1-1-1", &taken);
+    assert_ne!(other, id);
+    assert_eq!(other.len(), 6);
+}
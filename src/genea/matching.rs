@@ -0,0 +1,118 @@
+//! Matching up the people in two versions of a family tree, e.g. before and after
+//! part of it was renumbered.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{normalize_name, Genea, Person};
+
+#[cfg(test)]
+mod tests;
+
+/// Pairs people in the `old` version of the tree with the same person in the `new` one,
+/// returning a map from the old `Person` to the new one.
+///
+/// People with the same persistent id always match. Otherwise, people are matched by their
+/// name together with the names of their partners and parents, falling back to fewer of
+/// those as long as the match stays unambiguous. Finally, people who were renamed are
+/// matched by their partners, parents and children, or by their id. Since siblings
+/// share their parents, the parents alone are never enough for a match.
+/// People who were removed, or whose match is ambiguous, are left out.
+pub fn match_people(old: &Genea, new: &Genea) -> BTreeMap<Person, Person> {
+    let mut matches = BTreeMap::new();
+
+    let new_by_id: BTreeMap<&str, Person> = new
+        .people()
+        .filter_map(|p| Some((new[p].persistent_id.as_deref()?, p)))
+        .collect();
+    for old_person in old.people() {
+        if let Some(id) = &old[old_person].persistent_id {
+            if let Some(&new_person) = new_by_id.get(id.as_str()) {
+                matches.insert(old_person, new_person);
+            }
+        }
+    }
+
+    for keys in [
+        &[Key::Name, Key::Partners, Key::Parents][..],
+        &[Key::Name, Key::Parents],
+        &[Key::Name, Key::Partners],
+        &[Key::Name],
+        &[Key::Partners, Key::Parents, Key::Children],
+        &[Key::Id, Key::Partners, Key::Parents],
+    ] {
+        let matched_new: BTreeSet<Person> = matches.values().copied().collect();
+
+        let mut candidates: BTreeMap<Signature, (Vec<Person>, Vec<Person>)> = BTreeMap::new();
+        for old_person in old.people().filter(|p| !matches.contains_key(p)) {
+            let signature = keys.iter().map(|k| k.names(old, old_person)).collect();
+            candidates.entry(signature).or_default().0.push(old_person);
+        }
+        for new_person in new.people().filter(|p| !matched_new.contains(p)) {
+            let signature = keys.iter().map(|k| k.names(new, new_person)).collect();
+            candidates.entry(signature).or_default().1.push(new_person);
+        }
+
+        for (signature, (old_people, new_people)) in candidates {
+            let only_parents = keys
+                .iter()
+                .zip(&signature)
+                .all(|(key, names)| matches!(key, Key::Parents) || names.is_empty());
+            if only_parents {
+                continue;
+            }
+
+            if let ([old_person], [new_person]) = (&old_people[..], &new_people[..]) {
+                matches.insert(*old_person, *new_person);
+            }
+        }
+    }
+
+    matches
+}
+
+/// The names found for each of the keys being compared.
+type Signature = Vec<Vec<String>>;
+
+/// The things we compare when looking for the same person in two trees.
+#[derive(Copy, Clone)]
+enum Key {
+    Id,
+    Name,
+    Partners,
+    Parents,
+    Children,
+}
+
+impl Key {
+    /// The normalized (and sorted) names that `person` has for this key
+    /// (or, for [`Key::Id`], their id).
+    fn names(self, genea: &Genea, person: Person) -> Vec<String> {
+        let person_data = &genea[person];
+        let people: Vec<Person> = match self {
+            Key::Id => return vec![genea.id(person)],
+            Key::Name => vec![person],
+            Key::Partners => person_data
+                .parent_in
+                .iter()
+                .filter_map(|&p| genea[p].other_parent(person))
+                .collect(),
+            Key::Parents => person_data
+                .child_in
+                .iter()
+                .flat_map(|&p| genea[p].parents.iter().copied())
+                .collect(),
+            Key::Children => person_data
+                .parent_in
+                .iter()
+                .flat_map(|&p| genea[p].children.iter().copied())
+                .collect(),
+        };
+
+        let mut names: Vec<String> = people
+            .into_iter()
+            .map(|p| normalize_name(&genea[p].name))
+            .collect();
+        names.sort();
+        names
+    }
+}
@@ -0,0 +1,62 @@
+//! Finding the same people in two versions of a small tree.
+
+use super::match_people;
+use crate::genea::{Genea, ParseOptions};
+
+fn parse(text: &str) -> Genea {
+    Genea::from_genea_text("genea.doc", text, ParseOptions::default()).unwrap()
+}
+
+/// The matches, as pairs of ids in the old and the new tree.
+fn matched_ids(old: &Genea, new: &Genea) -> Vec<(String, String)> {
+    match_people(old, new)
+        .into_iter()
+        .map(|(o, n)| (old.id(o), new.id(n)))
+        .collect()
+}
+
+#[test]
+fn renumbered_people_are_matched_by_name_and_family() {
+    let old = parse(" 1 0 M 2 0 0 Skon\n 1 1 M 0 0 0 Sarek\n 1 2 M 0 0 0 Sybok\n");
+    let new =
+        parse(" 1 0 M 3 0 0 Skon\n 1 1 M 0 0 0 Solkar\n 1 2 M 0 0 0 Sarek\n 1 3 M 0 0 0 Sybok\n");
+    assert_eq!(
+        matched_ids(&old, &new),
+        [
+            ("1".to_string(), "1".to_string()),
+            ("1-1".to_string(), "1-2".to_string()),
+            ("1-2".to_string(), "1-3".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn persistent_ids_match_even_when_the_name_changes() {
+    let old = parse(" 1 0 M 1 0 0 Skon\n 1 1 M 0 0 0 #sarek Sarek\n");
+    let new = parse(" 1 0 M 1 0 0 Skon\n 1 1 M 0 0 0 #sarek Sarek of Vulcan\n");
+    assert!(matched_ids(&old, &new).contains(&("sarek".to_string(), "sarek".to_string())));
+}
+
+#[test]
+fn people_who_cannot_be_told_apart_are_not_matched() {
+    // Two children called Sarek, with nothing else to tell them apart,
+    // and whose ids changed when Surak was added.
+    let old = parse(" 1 0 M 2 0 0 Skon\n 1 1 M 0 0 0 Sarek\n 1 2 M 0 0 0 Sarek\n");
+    let new =
+        parse(" 1 0 M 0 0 0 Surak\n 2 0 M 2 0 0 Skon\n 2 1 M 0 0 0 Sarek\n 2 2 M 0 0 0 Sarek\n");
+    assert_eq!(
+        matched_ids(&old, &new),
+        [("1".to_string(), "2".to_string())]
+    );
+}
+
+#[test]
+fn partners_tell_people_with_the_same_name_apart() {
+    let old =
+        parse(" 1 0 M 2 0 0 Skon\n 1 1 M 0 1 0 Sarek\n 1 1 F 0 0 1 Amanda\n 1 2 M 0 0 0 Sarek\n");
+    let new =
+        parse(" 1 0 M 2 0 0 Skon\n 1 1 M 0 0 0 Sarek\n 1 2 M 0 1 0 Sarek\n 1 2 F 0 0 1 Amanda\n");
+    let matched = matched_ids(&old, &new);
+    assert!(matched.contains(&("1-1".to_string(), "1-2".to_string())));
+    assert!(matched.contains(&("1-2".to_string(), "1-1".to_string())));
+}
@@ -1,7 +1,7 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
-    path::Path,
     str::FromStr,
+    sync::Arc,
 };
 
 use anyhow::Context;
@@ -11,34 +11,114 @@ use regex::Regex;
 use crate::genea::Span;
 
 use super::{
-    error::{ParseError, ParseErrorKind},
-    Gender, Genea, HenryNumber, Partnership, PartnershipData, Person, PersonData, SpousalIndex,
+    error::{self, ParseError, ParseErrorKind, ParseErrors, Severity},
+    normalize_name,
+    source_map::SourceMap,
+    tokenizer, Gender, Genea, HenryNumber, Partnership, PartnershipData, Person, PersonData,
+    SpousalIndex,
 };
 
+mod format;
+#[cfg(test)]
+mod tests;
+
+pub use format::format_text;
+
 lazy_static::lazy_static! {
     static ref PERSON_LINE: Regex = Regex::new(
-        "(?P<henry> *(\\d+ +)+)\
+        "^(?P<henry> *(\\d+ +)+)\
         (?P<gender>[MF?])\\s*\
         (?P<numkids>\\d+)\\s+\
         (?P<numspouses>\\d+)\\s+\
         (?P<spouse>\\d+)\\s+\
-        (?P<altid>\\d+)?\\s*\
-        (?P<name>[^\\\\;]+)\
-        (\\\\(?P<comment>[^;]+))?\
+        (?P<altid>\\d+(\\.\\d+)*)?\\s*\
+        (#(?P<id>[A-Za-z][A-Za-z0-9_-]*)\\s+)?\
+        (?P<name>[^\\\\;\\s#][^\\\\;]*)\
+        (\\\\(?P<comment>[^;]*))?\
         (;(?P<private>.*))?"
     ).unwrap();
 }
 
+lazy_static::lazy_static! {
+    /// The layout of some older printouts: each generation is indented
+    /// and the henry numbers come after the name.
+    static ref INDENTED_PERSON_LINE: Regex = Regex::new(
+        "^(?P<indent> *)\
+        (#(?P<id>[A-Za-z][A-Za-z0-9_-]*)\\s+)?\
+        (?P<name>[^\\\\;\\s#][^\\\\;]*?)\\s+\
+        (?P<henry>(\\d+ +)+)\
+        (?P<gender>[MF?])\\s*\
+        (?P<numkids>\\d+)\\s+\
+        (?P<numspouses>\\d+)\\s+\
+        (?P<spouse>\\d+)\
+        (\\s+(?P<altid>\\d+(\\.\\d+)*))?\\s*\
+        (\\\\(?P<comment>[^;]*))?\
+        (;(?P<private>.*))?$"
+    ).unwrap();
+}
+
+/// The two ways that person lines are laid out in `genea.doc` printouts.
+/// The layout is detected from the first person line and must be used throughout.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// Henry numbers at the start of the line, then gender, counts, spousal index,
+    /// alternate henry number and finally the name.
+    HenryFirst,
+
+    /// The name, indented by generation, followed by the henry numbers, gender,
+    /// counts, spousal index and alternate henry number.
+    Indented,
+}
+
+impl Layout {
+    fn detect(line: &str) -> Option<Layout> {
+        [Layout::HenryFirst, Layout::Indented]
+            .into_iter()
+            .find(|layout| layout.regex().is_match(line))
+    }
+
+    fn regex(self) -> &'static Regex {
+        match self {
+            Layout::HenryFirst => &PERSON_LINE,
+            Layout::Indented => &INDENTED_PERSON_LINE,
+        }
+    }
+}
+
 lazy_static::lazy_static! {
     static ref MAINTAINER_LINE: Regex = Regex::new(
         r"Maintainer URL: <(?P<url>[^>]*)>"
     ).unwrap();
 }
 
+/// Options that control how a `genea.doc` file is parsed.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ParseOptions {
+    /// Require the person lines to be in depth-first order, as they appear in the printouts:
+    /// everybody after their parent, and spouses (followed by their children) right after
+    /// their partner. Otherwise the lines are put in that order based on their henry numbers.
+    pub strict_order: bool,
+}
+
 struct Parser {
+    /// The text being parsed, shared with any errors we report
+    source_map: Arc<SourceMap>,
+
+    options: ParseOptions,
+
     /// Initially true, becomes false once we see the first person's data
     preamble: bool,
 
+    /// Layout of the person lines, determined by the first one we see
+    layout: Option<Layout>,
+
+    /// For the indented layout, the number of spaces per generation,
+    /// determined by the first indented line
+    indent_unit: Option<usize>,
+
+    /// Errors that don't prevent us from making sense of the line they are on
+    errors: Vec<(usize, ParseErrorKind)>,
+
     /// We expect the input to be sorted. We maintain a stack of people as we go,
     /// popping people off once we encounter someone who is not their descendant.
     /// We only put the 'primary' people on here; secondary spouses are added to their partnership list instead.
@@ -49,103 +129,256 @@ struct Parser {
     by_secondary_henry_number: BTreeMap<HenryNumber, BTreeSet<Person>>,
     by_partners: BTreeMap<BTreeSet<Person>, Partnership>,
 
+    /// Every person line we have processed, kept so that we can cross-check
+    /// the declared counts once the whole tree is known.
+    person_lines: Vec<PersonLine>,
+
+    /// Henry numbers of the person lines that failed to parse. After an error we skip
+    /// the descendants of the most recent one, since they would only report the same
+    /// problem again, and we don't cross-check the counts of their ancestors.
+    failed_henry_numbers: Vec<HenryNumber>,
+
+    /// Alternate henry numbers that referred to somebody we had not seen yet.
+    /// They are resolved once the whole file has been read.
+    alt_id_references: Vec<AltIdReference>,
+
     /// The result thus far
     genea: Genea,
 }
 
+/// A person line that was successfully added to the tree.
+struct PersonLine {
+    line_num: usize,
+    person: Person,
+
+    /// For spouse lines, the primary person they are partnered with
+    /// and the partnership that was created for them.
+    spouse_of: Option<(Person, Partnership)>,
+
+    data: LineData,
+}
+
+/// A person line whose alternate henry number could not be matched when it was read.
+struct AltIdReference {
+    line_num: usize,
+    person: Person,
+    hn: HenryNumber,
+    hn_span: Span,
+}
+
 struct StackEntry {
     person: Person,
     partnership: Option<Partnership>,
 }
 
-pub fn parse_text(path: &Path, text: &str) -> anyhow::Result<Genea> {
+/// Parses the contents of a `genea.doc` file. `origin` describes where
+/// the text came from (e.g., a path or `<stdin>`) and is used in diagnostics.
+pub fn parse_text(
+    origin: impl std::fmt::Display,
+    text: &str,
+    options: ParseOptions,
+) -> anyhow::Result<Genea> {
     let mut lines = text.lines().zip(1..);
-    Ok(Parser {
+    let mut genea = Parser {
+        source_map: Arc::new(SourceMap::new(origin, text)),
+        options,
         preamble: true,
+        layout: None,
+        indent_unit: None,
+        errors: Default::default(),
         stack: Default::default(),
         genea: Default::default(),
         by_primary_henry_number: Default::default(),
         by_secondary_henry_number: Default::default(),
         by_partners: Default::default(),
+        person_lines: Default::default(),
+        failed_henry_numbers: Default::default(),
+        alt_id_references: Default::default(),
+    }
+    .parse_lines(&mut lines)?;
+
+    // Without any person lines, the blank lines at the end are part of the preamble.
+    if genea.people().next().is_some() {
+        let content_end = text.trim_end().len();
+        if let Some(i) = text[content_end..].find('\n') {
+            genea.epilogue = text[content_end + i + 1..].to_string();
+        }
     }
-    .parse_lines(path, &mut lines)?)
+
+    Ok(genea)
 }
 
 impl Parser {
     fn parse_lines(
         mut self,
-        path: &Path,
         lines: &mut dyn Iterator<Item = (&str, usize)>,
-    ) -> Result<Genea, ParseError> {
+    ) -> Result<Genea, ParseErrors> {
+        let mut person_lines = vec![];
         for (line, line_num) in lines {
-            let () = self
-                .parse_line(line, line_num)
-                .map_err(|source| ParseError {
-                    path: path.to_path_buf(),
-                    line_num,
-                    kind: source,
-                })?;
+            match self.read_line(line, line_num) {
+                Ok(Some(line_data)) => person_lines.push((line_num, line_data)),
+                Ok(None) => {}
+                Err(source) => {
+                    self.errors.push((line_num, source));
+
+                    // Remember which family the line belonged to, so that we don't
+                    // also complain that the family is missing somebody.
+                    let layout = self.layout.unwrap_or(Layout::HenryFirst);
+                    if let Some(hn) = tokenizer::henry_number(line, layout) {
+                        if !hn.ancestry.is_empty() {
+                            self.failed_henry_numbers.push(hn);
+                        }
+                    }
+                }
+            }
+
+            if self.options.strict_order {
+                for (line_num, line_data) in person_lines.drain(..) {
+                    self.add_line(line_num, line_data);
+                }
+            }
+        }
+
+        for (line_num, line_data) in depth_first_order(person_lines) {
+            self.add_line(line_num, line_data);
         }
 
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.extend(self.resolve_alt_id_references());
+        errors.extend(self.check_ancestry());
+        errors.extend(self.check_counts());
+        errors.extend(self.check_spousal_indices());
+        errors.extend(self.check_persistent_ids());
+        errors.extend(self.check_similar_names());
+        errors.sort_by_key(|&(line_num, _)| line_num);
+
+        let errors: Vec<ParseError> = errors
+            .into_iter()
+            .map(|(line_num, kind)| ParseError {
+                source_map: self.source_map.clone(),
+                line_num,
+                kind,
+            })
+            .collect();
+
+        if errors.iter().any(|e| e.kind.severity() == Severity::Error) {
+            return Err(ParseErrors {
+                source_map: self.source_map.clone(),
+                errors,
+            });
+        }
+
+        self.genea.warnings = errors;
+
         Ok(self.genea)
     }
 
-    fn parse_line(&mut self, line: &str, line_num: usize) -> Result<(), ParseErrorKind> {
+    /// Reads a single line, returning its data if it is a person line.
+    fn read_line(
+        &mut self,
+        line: &str,
+        line_num: usize,
+    ) -> Result<Option<LineData>, ParseErrorKind> {
         let line_trim = line.trim();
         if line_trim.is_empty() {
-            return Ok(());
+            if self.preamble {
+                self.genea.preamble.push_str(line);
+                self.genea.preamble.push('\n');
+            }
+            return Ok(None);
         }
 
-        if !PERSON_LINE.is_match(line) {
-            if self.preamble {
+        let layout = match self.layout {
+            Some(layout) => Some(layout).filter(|layout| layout.regex().is_match(line)),
+            None => Layout::detect(line),
+        };
+
+        let Some(layout) = layout else {
+            if self.preamble && !tokenizer::looks_like_person_line(line) {
+                self.genea.preamble.push_str(line);
+                self.genea.preamble.push('\n');
                 if let Some(m) = MAINTAINER_LINE.captures(line) {
                     self.genea.maintainer_link = Some(m.name("url").unwrap().as_str().to_string());
                 }
-                return Ok(());
+                return Ok(None);
             }
 
-            return Err(ParseErrorKind::MalformedLine);
+            // If the tokenizer is happy but the regular expression isn't, we can't say much more.
+            self.preamble = false;
+            let layout = self.layout.unwrap_or(Layout::HenryFirst);
+            return Err(tokenizer::diagnose(line, line_num, layout)
+                .unwrap_or(ParseErrorKind::MalformedLine));
         };
 
         self.preamble = false;
+        self.layout = Some(layout);
+
+        // Prefer the tokenizer's explanation, which points at the field that is wrong,
+        // but keep our own error if it can't find one.
+        let line_data = match LineData::parse(line, layout) {
+            Ok(line_data) => line_data,
+            Err(e) => return Err(tokenizer::diagnose(line, line_num, layout).unwrap_or(e)),
+        };
+
+        if let Err(e) = self.check_indentation(line_num, &line_data) {
+            self.errors.push((line_num, e));
+        }
+
+        if let Err(e) = Self::check_alt_id(line, line_num, &line_data) {
+            self.errors.push((line_num, e));
+        }
 
-        let line_data =
-            &LineData::from_str(line).with_context(|| format!("expected person data"))?;
+        Ok(Some(line_data))
+    }
+
+    /// Adds the person on a line to the tree. Lines must be added in depth-first order.
+    fn add_line(&mut self, line_num: usize, line_data: LineData) {
+        // Skip the descendants of a line that failed to parse.
+        let hn = line_data.primary_henry_number.clone();
+        if self
+            .failed_henry_numbers
+            .iter()
+            .any(|failed_hn| failed_hn.is_prefix_of(&hn) && *failed_hn != hn)
+        {
+            return;
+        }
 
+        if let Err(e) = self.parse_person_line(line_num, line_data) {
+            self.errors.push((line_num, e));
+            if !hn.ancestry.is_empty() {
+                self.failed_henry_numbers.push(hn);
+            }
+        }
+    }
+
+    fn parse_person_line(
+        &mut self,
+        line_num: usize,
+        line_data: LineData,
+    ) -> Result<(), ParseErrorKind> {
         let make_span = |r: &std::ops::Range<usize>| range_to_span(line_num, r);
 
+        // Everybody descends from a root ancestor, so the henry number needs at least one generation.
+        if line_data.primary_henry_number.ancestry.is_empty() {
+            return Err(ParseErrorKind::NotRootAncestor {
+                line_name: line_data.name.clone(),
+                line_name_span: make_span(&line_data.name_range),
+                line_hn_span: make_span(&line_data.primary_henry_number_range),
+            });
+        }
+
         // Remove people from the stack unless they are either an ancestor or partner.
         self.pop_stack(&line_data);
 
         // If we have a secondary number, see if this person has already been created.
-        let existing_people = match &line_data.secondary_henry_number {
-            None => {
-                // If we don't have a secondary number recorded, double check that there is nobody
-                None
-            }
-            Some(hn) => {
-                if let Some(set) = self.by_primary_henry_number.get(hn) {
-                    if let Some(&p) = set.iter().find(|&&p| self.genea[p].name == line_data.name) {
-                        Some(p)
-                    } else {
-                        return Err(ParseErrorKind::NoMatchingPerson {
-                            name: line_data.name.clone(),
-                            hn: hn.clone(),
-                            hn_span: make_span(
-                                line_data.secondary_henry_number_range.as_ref().unwrap(),
-                            ),
-                            existing_names: set
-                                .iter()
-                                .map(|&p| self.genea[p].name.clone())
-                                .collect(),
-                            existing_name_spans: set.iter().map(|&p| self.genea[p].span).collect(),
-                        });
-                    }
-                } else {
-                    None
-                }
-            }
-        };
+        // If not, they may appear later in the file; we'll check once we've read it all.
+        let existing_people = line_data.secondary_henry_number.as_ref().and_then(|hn| {
+            self.by_primary_henry_number
+                .get(hn)?
+                .iter()
+                .copied()
+                .find(|&p| line_data.names().any(|name| self.genea[p].is_named(name)))
+        });
 
         let person = match existing_people {
             None => {
@@ -155,7 +388,11 @@ impl Parser {
                     child_in: Default::default(),
                     parent_in: Default::default(),
                     name: line_data.name.clone(),
+                    aliases: line_data.aliases.clone(),
+                    persistent_id: line_data.persistent_id.clone(),
                     comments: line_data.comments.clone(),
+                    blank_comments: line_data.comments_range.is_some()
+                        && line_data.comments.is_empty(),
                     private_comments: line_data.private_comments.clone(),
                     henry_number: if line_data.spousal_index.is_primary() {
                         Some(line_data.primary_henry_number.clone())
@@ -174,14 +411,32 @@ impl Parser {
                 );
                 if let Some(hn) = &line_data.secondary_henry_number {
                     Self::insert_by_henry_number(&mut self.by_secondary_henry_number, hn, person);
+                    self.alt_id_references.push(AltIdReference {
+                        line_num,
+                        person,
+                        hn: hn.clone(),
+                        hn_span: make_span(
+                            line_data.secondary_henry_number_range.as_ref().unwrap(),
+                        ),
+                    });
                 }
 
                 person
             }
 
             Some(existing_person) => {
+                let existing_lines: Vec<&PersonLine> = self
+                    .person_lines
+                    .iter()
+                    .filter(|l| l.person == existing_person)
+                    .collect();
                 let existing_data = &mut self.genea[existing_person];
-                Self::merge_person(line_num, existing_data, line_data)?;
+                Self::merge_person(line_num, existing_data, &existing_lines, &line_data)?;
+                Self::insert_by_henry_number(
+                    &mut self.by_primary_henry_number,
+                    &line_data.primary_henry_number,
+                    existing_person,
+                );
                 existing_person
             }
         };
@@ -192,25 +447,42 @@ impl Parser {
             let top = self
                 .stack
                 .last()
-                .ok_or_else(|| anyhow::anyhow!("expected partner on the stack"))?;
-
-            let partner_henry_number = self.genea[top.person].henry_number().unwrap();
-            if *partner_henry_number != line_data.primary_henry_number {
-                return Err(ParseErrorKind::TopNotPartner {
+                .ok_or_else(|| ParseErrorKind::NoPartnerForSpouse {
                     line_name: line_data.name.clone(),
                     line_name_span: make_span(&line_data.name_range),
                     line_hn: line_data.primary_henry_number.clone(),
-                    line_hn_span: make_span(&line_data.primary_henry_number_range),
                     spousal_index_span: make_span(&line_data.spousal_index_range),
-                    top_name: self.genea[top.person].name.clone(),
-                    top_span: self.genea[top.person].span,
-                    top_hn: self.genea[top.person].henry_number.clone().unwrap(),
-                });
+                })?;
+
+            let partner_henry_number = self.genea[top.person]
+                .henry_number()
+                .expect("only people with a henry number of their own are put on the stack");
+            if *partner_henry_number != line_data.primary_henry_number {
+                return Err(ParseErrorKind::TopNotPartner(Box::new(
+                    error::TopNotPartner {
+                        line_name: line_data.name.clone(),
+                        line_hn: line_data.primary_henry_number.clone(),
+                        line_hn_span: make_span(&line_data.primary_henry_number_range),
+                        spousal_index_span: make_span(&line_data.spousal_index_range),
+                        top_name: self.genea[top.person].name.clone(),
+                        top_span: self.genea[top.person].span,
+                        top_hn: partner_henry_number.clone(),
+                    },
+                )));
             }
 
             // Check if the partnership already exists.
-            self.partner_top(Some(person));
-
+            let partner = top.person;
+            let partnership = self.partner_top(Some(person));
+            self.genea[partnership].spousal_index = Some(line_data.spousal_index);
+            self.genea[partnership].num_kids = line_data.num_kids;
+
+            self.person_lines.push(PersonLine {
+                line_num,
+                person,
+                spouse_of: Some((partner, partnership)),
+                data: line_data,
+            });
             return Ok(());
         }
 
@@ -219,35 +491,60 @@ impl Parser {
             let top = self
                 .stack
                 .last()
-                .ok_or_else(|| anyhow::anyhow!("no root ancestor found on stack"))?;
-            let parent = top.person;
-
-            if *self.genea[parent].henry_number().unwrap() != parent_hn {
-                return Err(ParseErrorKind::TopNotParent {
+                .ok_or_else(|| ParseErrorKind::NoParentFound {
                     line_name: line_data.name.clone(),
                     line_name_span: make_span(&line_data.name_range),
-                    line_hn: line_data.primary_henry_number.clone(),
                     line_hn_span: make_span(&line_data.primary_henry_number_range),
-                    top_name: self.genea[parent].name.clone(),
-                    top_hn: self.genea[parent].henry_number.clone().unwrap(),
-                    top_span: self.genea[parent].span,
-                });
+                    parent_hn: parent_hn.clone(),
+                })?;
+            let parent = top.person;
+            let top_hn = self.genea[parent]
+                .henry_number()
+                .expect("only people with a henry number of their own are put on the stack");
+
+            if *top_hn != parent_hn {
+                return Err(ParseErrorKind::TopNotParent(Box::new(
+                    error::TopNotParent {
+                        line_name: line_data.name.clone(),
+                        line_name_span: make_span(&line_data.name_range),
+                        line_hn: line_data.primary_henry_number.clone(),
+                        line_hn_span: make_span(&line_data.primary_henry_number_range),
+                        top_name: self.genea[parent].name.clone(),
+                        top_hn: top_hn.clone(),
+                        top_span: self.genea[parent].span,
+                    },
+                )));
             }
 
             // Load partnership from top of the stack, creating one if needed
             let partnership = top.partnership.unwrap_or_else(|| self.partner_top(None));
 
             for &sibling in &self.genea[partnership].children {
-                let child_hn = self.genea[sibling].henry_number().unwrap();
+                let child_hn = self.genea[sibling]
+                    .henry_number()
+                    .expect("children are descendants, so they have a henry number of their own");
                 if line_data.primary_henry_number == *child_hn {
-                    return Err(ParseErrorKind::SiblingWithSameHenryNumber {
-                        line_name: line_data.name.clone(),
-                        line_name_span: make_span(&line_data.name_range),
-                        line_hn: line_data.primary_henry_number.clone(),
-                        line_hn_span: make_span(&line_data.primary_henry_number_range),
-                        sibling_name: self.genea[sibling].name.clone(),
-                        sibling_span: self.genea[sibling].span,
-                    });
+                    // Suggest the henry number after the last of the parent's children.
+                    let last_child_hn = self.genea[parent]
+                        .parent_in
+                        .iter()
+                        .flat_map(|&p| &self.genea[p].children)
+                        .filter_map(|&c| self.genea[c].henry_number())
+                        .filter(|hn| hn.parent().as_ref() == Some(&parent_hn))
+                        .max()
+                        .expect("the sibling with the same henry number is one of the children");
+
+                    return Err(ParseErrorKind::SiblingWithSameHenryNumber(Box::new(
+                        error::SiblingWithSameHenryNumber {
+                            line_name: line_data.name.clone(),
+                            line_name_span: make_span(&line_data.name_range),
+                            line_hn: line_data.primary_henry_number.clone(),
+                            line_hn_span: make_span(&line_data.primary_henry_number_range),
+                            suggested_hn: last_child_hn.next_sibling(),
+                            sibling_name: self.genea[sibling].name.clone(),
+                            sibling_span: self.genea[sibling].span,
+                        },
+                    )));
                 }
             }
 
@@ -255,32 +552,580 @@ impl Parser {
                 self.genea[person].child_in = Some(partnership);
                 self.genea[partnership].children.push(person);
             }
-        } else {
-            assert!(
-                line_data.primary_henry_number.is_root_ancestor(),
-                "on line {line_num}, not a root ancestor"
-            );
-            assert!(
-                self.stack.is_empty(),
-                "on line {line_num}, stack not fully popped for root ancestor"
-            );
-        }
-
-        assert!(
-            self.genea[person].henry_number.is_some(),
-            "on line {line_num}, person should be primary descendant"
-        );
+        } else if let Some(top) = self.stack.last() {
+            // The only thing left on the stack for a root ancestor is an earlier
+            // root ancestor with the same henry number.
+            let last_root_hn = self
+                .by_primary_henry_number
+                .keys()
+                .filter(|hn| hn.is_root_ancestor())
+                .max()
+                .expect("the root ancestor on the stack was recorded by henry number");
+
+            return Err(ParseErrorKind::DuplicateRootAncestor(Box::new(
+                error::DuplicateRootAncestor {
+                    line_name: line_data.name.clone(),
+                    line_name_span: make_span(&line_data.name_range),
+                    line_hn: line_data.primary_henry_number.clone(),
+                    line_hn_span: make_span(&line_data.primary_henry_number_range),
+                    suggested_hn: last_root_hn.next_sibling(),
+                    other_name: self.genea[top.person].name.clone(),
+                    other_span: self.genea[top.person].span,
+                },
+            )));
+        }
+
+        if self.genea[person].henry_number.is_none() {
+            return Err(ParseErrorKind::NotPrimaryDescendant {
+                line_name: line_data.name.clone(),
+                line_name_span: make_span(&line_data.name_range),
+                spousal_index_span: make_span(&line_data.spousal_index_range),
+            });
+        }
+
         self.stack.push(StackEntry {
             person,
             partnership: None,
         });
 
+        self.person_lines.push(PersonLine {
+            line_num,
+            person,
+            spouse_of: None,
+            data: line_data,
+        });
+
+        Ok(())
+    }
+
+    /// Compact alternate henry numbers that end in zeros can be read in two ways;
+    /// we go with one digit per generation, but say so.
+    fn check_alt_id(
+        line: &str,
+        line_num: usize,
+        line_data: &LineData,
+    ) -> Result<(), ParseErrorKind> {
+        let (Some(hn), Some(range)) = (
+            &line_data.secondary_henry_number,
+            &line_data.secondary_henry_number_range,
+        ) else {
+            return Ok(());
+        };
+
+        let alt_id = &line[range.clone()];
+        if let Some(other_hn) = HenryNumber::other_alt_str_reading(alt_id) {
+            return Err(ParseErrorKind::AltIdEndsInZeros {
+                alt_id: alt_id.to_string(),
+                alt_id_span: range_to_span(line_num, range),
+                hn: hn.clone(),
+                other_hn,
+            });
+        }
+
         Ok(())
     }
 
+    /// In the indented layout, each generation is indented one step further than
+    /// the one before (spouses are indented like their partner). The size of a step
+    /// is taken from the first line that is not a root ancestor.
+    fn check_indentation(
+        &mut self,
+        line_num: usize,
+        line_data: &LineData,
+    ) -> Result<(), ParseErrorKind> {
+        let Some(indent_range) = &line_data.indent_range else {
+            return Ok(());
+        };
+
+        let indent = indent_range.len();
+        let depth = line_data
+            .primary_henry_number
+            .ancestry
+            .len()
+            .saturating_sub(1);
+        if depth > 0 && self.indent_unit.is_none() {
+            self.indent_unit = Some(if indent >= depth { indent / depth } else { 2 });
+        }
+
+        let expected = depth * self.indent_unit.unwrap_or(0);
+        if indent != expected {
+            return Err(ParseErrorKind::WrongIndentation(Box::new(
+                error::WrongIndentation {
+                    line_name: line_data.name.clone(),
+                    line_name_span: range_to_span(line_num, &line_data.name_range),
+                    line_hn: line_data.primary_henry_number.clone(),
+                    line_hn_span: range_to_span(line_num, &line_data.primary_henry_number_range),
+                    expected,
+                    found: indent,
+                },
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Compares the number of kids and spouses declared on each line against
+    /// the tree we actually built.
+    ///
+    /// On a spouse line, the kids are those of that particular partnership.
+    /// On a primary line, the kids are those from all of the person's partnerships
+    /// and the spouses are the spouse lines listed beneath them
+    /// (spouse lines themselves do not list spouses).
+    ///
+    /// The counts come from the paper records, so mismatches are only warnings.
+    fn check_counts(&self) -> Vec<(usize, ParseErrorKind)> {
+        let mut warnings = vec![];
+        for person_line in &self.person_lines {
+            let PersonLine {
+                line_num,
+                person,
+                spouse_of,
+                data: line_data,
+            } = person_line;
+
+            // If one of the descendants failed to parse, the counts won't line up anyway.
+            if self
+                .failed_henry_numbers
+                .iter()
+                .any(|hn| line_data.primary_henry_number.is_prefix_of(hn))
+            {
+                continue;
+            }
+            let make_span = |r: &std::ops::Range<usize>| range_to_span(*line_num, r);
+
+            let (num_kids, children): (usize, Vec<Person>) = match spouse_of {
+                Some((_, partnership)) => (
+                    line_data.num_kids,
+                    self.genea[*partnership].children.clone(),
+                ),
+                None => (
+                    self.genea[*person].num_kids,
+                    self.genea[*person]
+                        .parent_in
+                        .iter()
+                        .flat_map(|&p| &self.genea[p].children)
+                        .copied()
+                        .collect(),
+                ),
+            };
+
+            if children.len() != num_kids {
+                warnings.push((
+                    *line_num,
+                    ParseErrorKind::WrongNumberOfKids {
+                        name: line_data.name.clone(),
+                        num_kids,
+                        num_kids_span: make_span(&line_data.num_kids_range),
+                        child_names: children
+                            .iter()
+                            .map(|&c| self.genea[c].name.clone())
+                            .collect(),
+                        child_spans: children.iter().map(|&c| self.genea[c].span).collect(),
+                    },
+                ));
+            }
+
+            if spouse_of.is_some() {
+                continue;
+            }
+
+            let spouse_lines: Vec<&PersonLine> = self
+                .person_lines
+                .iter()
+                .filter(|l| matches!(l.spouse_of, Some((p, _)) if p == *person))
+                .collect();
+
+            if spouse_lines.len() != self.genea[*person].num_spouses {
+                warnings.push((
+                    *line_num,
+                    ParseErrorKind::WrongNumberOfSpouses {
+                        name: line_data.name.clone(),
+                        num_spouses: self.genea[*person].num_spouses,
+                        num_spouses_span: make_span(&line_data.num_spouses_range),
+                        spouse_names: spouse_lines.iter().map(|l| l.data.name.clone()).collect(),
+                        spouse_spans: spouse_lines
+                            .iter()
+                            .map(|l| range_to_span(l.line_num, &l.data.name_range))
+                            .collect(),
+                    },
+                ));
+            }
+        }
+
+        warnings
+    }
+
+    /// Links each alternate henry number that referred forward in the file to the person
+    /// it names, merging the two people created for them.
+    fn resolve_alt_id_references(&mut self) -> Vec<(usize, ParseErrorKind)> {
+        let mut errors = vec![];
+
+        let mut references = std::mem::take(&mut self.alt_id_references);
+        for i in 0..references.len() {
+            let AltIdReference {
+                line_num,
+                person,
+                ref hn,
+                hn_span,
+            } = references[i];
+
+            // If the line for `hn` failed to parse, we already reported that.
+            if self.failed_henry_numbers.iter().any(|f| f.is_prefix_of(hn)) {
+                continue;
+            }
+
+            let person_data = &self.genea[person];
+            let name = &person_data.name;
+            let Some(set) = self.by_primary_henry_number.get(hn) else {
+                errors.push((
+                    line_num,
+                    ParseErrorKind::UnresolvedAltId {
+                        name: name.clone(),
+                        name_span: self.genea[person].span,
+                        hn: hn.clone(),
+                        hn_span,
+                    },
+                ));
+                continue;
+            };
+
+            let target = set.iter().copied().find(|&p| {
+                std::iter::once(name)
+                    .chain(&person_data.aliases)
+                    .any(|n| self.genea[p].is_named(n))
+            });
+            let Some(target) = target else {
+                // Rank the people we did find by how close their names are.
+                let mut existing: Vec<Person> = set.iter().copied().collect();
+                existing.sort_by_key(|&p| edit_distance(name, &self.genea[p].name));
+                let suggestion = existing
+                    .first()
+                    .map(|&p| &self.genea[p].name)
+                    .filter(|n| edit_distance(name, n) * 3 <= name.len().max(n.len()))
+                    .cloned();
+
+                errors.push((
+                    line_num,
+                    ParseErrorKind::NoMatchingPerson(Box::new(error::NoMatchingPerson {
+                        name: name.clone(),
+                        name_span: person_data.span,
+                        hn: hn.clone(),
+                        hn_span,
+                        existing_names: existing
+                            .iter()
+                            .map(|&p| self.genea[p].name.clone())
+                            .collect(),
+                        existing_name_spans: existing.iter().map(|&p| self.genea[p].span).collect(),
+                        suggestion,
+                    })),
+                ));
+                continue;
+            };
+
+            // The line for `hn` may itself have referred back to `person`, in which case
+            // they were merged when it was read.
+            if target == person {
+                continue;
+            }
+
+            let Some(target_line) = self
+                .person_lines
+                .iter()
+                .find(|l| l.person == target && l.data.primary_henry_number == *hn)
+            else {
+                continue;
+            };
+
+            let existing_lines: Vec<&PersonLine> = self
+                .person_lines
+                .iter()
+                .filter(|l| l.person == person)
+                .collect();
+            if let Err(e) = Self::merge_person(
+                target_line.line_num,
+                &mut self.genea[person],
+                &existing_lines,
+                &target_line.data,
+            ) {
+                errors.push((target_line.line_num, e));
+                continue;
+            }
+
+            let renumber = self.genea.replace_person(target, person);
+            self.renumber_people(&renumber);
+            for reference in &mut references[i + 1..] {
+                reference.person = renumber(reference.person);
+            }
+        }
+
+        errors
+    }
+
+    /// Updates the people we have recorded after `Genea::replace_person`.
+    fn renumber_people(&mut self, renumber: &impl Fn(Person) -> Person) {
+        for map in [
+            &mut self.by_primary_henry_number,
+            &mut self.by_secondary_henry_number,
+        ] {
+            for set in map.values_mut() {
+                *set = set.iter().map(|&p| renumber(p)).collect();
+            }
+        }
+
+        self.by_partners = std::mem::take(&mut self.by_partners)
+            .into_iter()
+            .map(|(parents, partnership)| {
+                (parents.into_iter().map(renumber).collect(), partnership)
+            })
+            .collect();
+
+        for entry in &mut self.stack {
+            entry.person = renumber(entry.person);
+        }
+
+        for line in &mut self.person_lines {
+            line.person = renumber(line.person);
+            if let Some((partner, _)) = &mut line.spouse_of {
+                *partner = renumber(*partner);
+            }
+        }
+    }
+
+    /// Checks for family relationships that cannot be right. Since alternate henry numbers
+    /// let one person appear on several lines, a typo can make somebody their own spouse,
+    /// their own ancestor, a child of their own partner or a parent of their own sibling.
+    fn check_ancestry(&self) -> Vec<(usize, ParseErrorKind)> {
+        let mut errors = vec![];
+        let genea = &self.genea;
+        let name_span = |p: Person| genea[p].span;
+
+        for line in &self.person_lines {
+            if let Some((partner, _)) = line.spouse_of {
+                if partner == line.person {
+                    errors.push((
+                        line.line_num,
+                        ParseErrorKind::SelfPartnership {
+                            name: genea[partner].name.clone(),
+                            name_span: name_span(partner),
+                            spouse_span: range_to_span(line.line_num, &line.data.name_range),
+                        },
+                    ));
+                }
+            }
+        }
+
+        for partnership in genea.partnerships() {
+            let PartnershipData {
+                parents, children, ..
+            } = &genea[partnership];
+            for &parent in parents {
+                let Some(parent_child_in) = genea[parent].child_in else {
+                    continue;
+                };
+
+                for &partner in parents {
+                    if partner != parent && genea[parent_child_in].parents.contains(&partner) {
+                        errors.push((
+                            name_span(parent).line_num,
+                            ParseErrorKind::ChildOfPartner {
+                                name: genea[parent].name.clone(),
+                                name_span: name_span(parent),
+                                partner_name: genea[partner].name.clone(),
+                                partner_span: name_span(partner),
+                            },
+                        ));
+                    }
+                }
+
+                for &child in children {
+                    if child != parent && genea[child].child_in == Some(parent_child_in) {
+                        errors.push((
+                            name_span(parent).line_num,
+                            ParseErrorKind::ParentAndSibling {
+                                name: genea[parent].name.clone(),
+                                name_span: name_span(parent),
+                                child_name: genea[child].name.clone(),
+                                child_span: name_span(child),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+
+        // Walk up from each person through their parents, looking for somebody
+        // who is already on the path we took to get there.
+        #[derive(Copy, Clone, PartialEq, Eq)]
+        enum Visit {
+            New,
+            OnPath,
+            Done,
+        }
+
+        fn visit(
+            genea: &Genea,
+            person: Person,
+            visits: &mut [Visit],
+            path: &mut Vec<Person>,
+            cycles: &mut Vec<Vec<Person>>,
+        ) {
+            match visits[person.0] {
+                Visit::Done => return,
+                Visit::OnPath => {
+                    let start = path.iter().position(|&p| p == person).unwrap();
+                    cycles.push(path[start..].to_vec());
+                    return;
+                }
+                Visit::New => {}
+            }
+
+            visits[person.0] = Visit::OnPath;
+            path.push(person);
+            if let Some(child_in) = genea[person].child_in {
+                for &parent in &genea[child_in].parents {
+                    visit(genea, parent, visits, path, cycles);
+                }
+            }
+            path.pop();
+            visits[person.0] = Visit::Done;
+        }
+
+        let mut visits = vec![Visit::New; genea.people().count()];
+        let mut cycles = vec![];
+        for person in genea.people() {
+            visit(genea, person, &mut visits, &mut vec![], &mut cycles);
+        }
+
+        for cycle in cycles {
+            // The path ends with the ancestor who is `person` again.
+            let person = cycle[0];
+            let ancestors: Vec<Person> = cycle[1..].iter().copied().chain([person]).collect();
+            errors.push((
+                name_span(person).line_num,
+                ParseErrorKind::AncestryCycle {
+                    name: genea[person].name.clone(),
+                    name_span: name_span(person),
+                    ancestor_names: ancestors.iter().map(|&p| genea[p].name.clone()).collect(),
+                    ancestor_spans: ancestors.iter().map(|&p| name_span(p)).collect(),
+                },
+            ));
+        }
+
+        errors
+    }
+
+    /// Checks that the spouses listed beneath each primary person are numbered
+    /// 1, 2, 3... in the order they appear, without gaps or duplicates.
+    fn check_spousal_indices(&self) -> Vec<(usize, ParseErrorKind)> {
+        let mut errors = vec![];
+
+        let mut lines: Vec<&PersonLine> = self.person_lines.iter().collect();
+        lines.sort_by_key(|l| l.line_num);
+
+        let mut num_spouses: BTreeMap<Person, usize> = BTreeMap::new();
+        for line in lines {
+            let Some((partner, _)) = line.spouse_of else {
+                continue;
+            };
+
+            let expected = num_spouses.entry(partner).or_default();
+            *expected += 1;
+
+            if line.data.spousal_index != SpousalIndex(*expected) {
+                errors.push((
+                    line.line_num,
+                    ParseErrorKind::SpousalIndexOutOfSequence(Box::new(
+                        error::SpousalIndexOutOfSequence {
+                            name: line.data.name.clone(),
+                            spousal_index: line.data.spousal_index,
+                            spousal_index_span: range_to_span(
+                                line.line_num,
+                                &line.data.spousal_index_range,
+                            ),
+                            expected: *expected,
+                            partner_name: self.genea[partner].name.clone(),
+                            partner_span: self.genea[partner].span,
+                        },
+                    )),
+                ));
+            }
+        }
+
+        errors
+    }
+
+    /// Checks that no two people were given the same persistent id.
+    fn check_persistent_ids(&self) -> Vec<(usize, ParseErrorKind)> {
+        let mut errors = vec![];
+
+        let mut lines: Vec<&PersonLine> = self.person_lines.iter().collect();
+        lines.sort_by_key(|l| l.line_num);
+
+        let mut first_uses: BTreeMap<&str, (Person, Span)> = BTreeMap::new();
+        for line in lines {
+            let (Some(id), Some(id_range)) =
+                (&line.data.persistent_id, &line.data.persistent_id_range)
+            else {
+                continue;
+            };
+
+            let id_span = range_to_span(line.line_num, id_range);
+            let (other, other_span) = *first_uses.entry(id).or_insert((line.person, id_span));
+            if other != line.person {
+                errors.push((
+                    line.line_num,
+                    ParseErrorKind::DuplicatePersistentId(Box::new(error::DuplicatePersistentId {
+                        id: id.clone(),
+                        id_span,
+                        name: line.data.name.clone(),
+                        other_name: self.genea[other].name.clone(),
+                        other_span,
+                    })),
+                ));
+            }
+        }
+
+        errors
+    }
+
+    /// Lints names that compare as equal but are not written identically,
+    /// such as `T'Melis` and `T’Melis`; the first spelling in the file is taken as the intended one.
+    fn check_similar_names(&self) -> Vec<(usize, ParseErrorKind)> {
+        let mut errors = vec![];
+
+        let mut lines: Vec<&PersonLine> = self.person_lines.iter().collect();
+        lines.sort_by_key(|l| l.line_num);
+
+        let mut first_spellings: BTreeMap<String, (&str, Span)> = BTreeMap::new();
+        for line in lines {
+            let span = range_to_span(line.line_num, &line.data.name_range);
+            for name in line.data.names() {
+                let (first_name, first_span) = *first_spellings
+                    .entry(normalize_name(name))
+                    .or_insert((name, span));
+                if name != first_name {
+                    errors.push((
+                        line.line_num,
+                        ParseErrorKind::SimilarNames {
+                            name: name.to_string(),
+                            name_span: span,
+                            other_name: first_name.to_string(),
+                            other_span: first_span,
+                        },
+                    ));
+                }
+            }
+        }
+
+        errors
+    }
+
     /// Set the current partnership of the top of the stack to `partner`
     fn partner_top(&mut self, partner: Option<Person>) -> Partnership {
-        let top = self.stack.last_mut().unwrap();
+        let top = self
+            .stack
+            .last_mut()
+            .expect("partners are only looked up for the person atop the stack");
 
         let mut parents = BTreeSet::default();
         parents.insert(top.person);
@@ -289,7 +1134,10 @@ impl Parser {
         let p = *self.by_partners.entry(parents.clone()).or_insert_with(|| {
             let partnership = self.genea.add_partnership(PartnershipData {
                 parents: parents.clone(),
+                primary_parent: top.person,
                 children: vec![],
+                spousal_index: None,
+                num_kids: 0,
             });
             for &p in &parents {
                 self.genea[p].parent_in.push(partnership);
@@ -307,9 +1155,7 @@ impl Parser {
         hn: &HenryNumber,
         person: Person,
     ) {
-        map.entry(hn.clone())
-            .or_insert_with(Default::default)
-            .insert(person);
+        map.entry(hn.clone()).or_default().insert(person);
     }
 
     /// Pops entries off the stack that are children of `line_data`
@@ -317,7 +1163,10 @@ impl Parser {
         let hn = &line_data.primary_henry_number;
         while let Some(&StackEntry { person: top, .. }) = self.stack.last() {
             let top_data = &self.genea[top];
-            if top_data.henry_number().unwrap().is_prefix_of(hn) {
+            let top_hn = top_data
+                .henry_number()
+                .expect("only people with a henry number of their own are put on the stack");
+            if top_hn.is_prefix_of(hn) {
                 break;
             }
 
@@ -325,71 +1174,302 @@ impl Parser {
         }
     }
 
+    /// Merges a line for a person who already appeared on `existing_lines` into their data.
+    /// Every field must agree, with these exceptions:
+    ///
+    /// * the lines need only share one spelling of the name; the first line's name is kept
+    ///   and any others become aliases;
+    /// * a known gender replaces `?`;
+    /// * the henry number and counts come from the primary line (there can only be one),
+    ///   though a spouse line cannot declare more kids than the primary line's total;
+    /// * comments, private comments and the persistent id may be given on just one of the lines.
     fn merge_person(
         line_num: usize,
         existing_data: &mut PersonData,
+        existing_lines: &[&PersonLine],
         line_data: &LineData,
     ) -> Result<(), ParseErrorKind> {
-        if existing_data.name != line_data.name {
+        let make_span = |r: &std::ops::Range<usize>| range_to_span(line_num, r);
+
+        // Span of a field on whichever existing line it came from, if we still have that line.
+        let other_span =
+            |matches: &dyn Fn(&LineData) -> bool,
+             range: &dyn Fn(&LineData) -> &std::ops::Range<usize>| {
+                existing_lines
+                    .iter()
+                    .find(|l| matches(&l.data))
+                    .map(|l| range_to_span(l.line_num, range(&l.data)))
+                    .unwrap_or(existing_data.span)
+            };
+
+        if !line_data.names().any(|name| existing_data.is_named(name)) {
             return Err(ParseErrorKind::MismatchedName {
                 expected_name: existing_data.name.clone(),
+                existing: existing_data.span,
                 found_name: line_data.name.clone(),
+                found: make_span(&line_data.name_range),
             });
         }
 
+        for name in line_data.names() {
+            if !existing_data.is_named(name) {
+                existing_data.aliases.push(name.to_string());
+            }
+        }
+
+        if let Some(id) = &line_data.persistent_id {
+            match &existing_data.persistent_id {
+                None => existing_data.persistent_id = Some(id.clone()),
+                Some(other_id) if other_id == id => {}
+                Some(other_id) => {
+                    return Err(ParseErrorKind::DifferentPersistentIds(Box::new(
+                        error::DifferentPersistentIds {
+                            name: line_data.name.clone(),
+                            id: id.clone(),
+                            id_span: make_span(line_data.persistent_id_range.as_ref().unwrap()),
+                            other_id: other_id.clone(),
+                            other_span: other_span(
+                                &|l| l.persistent_id.as_ref() == Some(other_id),
+                                &|l| l.persistent_id_range.as_ref().unwrap(),
+                            ),
+                        },
+                    )));
+                }
+            }
+        }
+
+        if line_data.gender != existing_data.gender {
+            match (existing_data.gender, line_data.gender) {
+                (_, Gender::Unknown) => {}
+                (Gender::Unknown, gender) => existing_data.gender = gender,
+                (other_gender, gender) => {
+                    return Err(ParseErrorKind::DifferentGender {
+                        name: line_data.name.clone(),
+                        gender,
+                        gender_span: make_span(&line_data.gender_range),
+                        other_gender,
+                        other_span: other_span(&|l| l.gender == other_gender, &|l| &l.gender_range),
+                    });
+                }
+            }
+        }
+
         if line_data.spousal_index.is_primary() {
             if let Some(hn) = &existing_data.henry_number {
                 return Err(ParseErrorKind::TwoPrimaryHenryNumbers {
                     name: existing_data.name.clone(),
                     hn: hn.clone(),
+                    hn_span: make_span(&line_data.primary_henry_number_range),
+                    other_span: other_span(&|l| l.spousal_index.is_primary(), &|l| {
+                        &l.primary_henry_number_range
+                    }),
                 });
             }
+
+            if let Some(spouse_line) = existing_lines
+                .iter()
+                .find(|l| l.spouse_of.is_some() && l.data.num_kids > line_data.num_kids)
+            {
+                return Err(ParseErrorKind::MoreKidsThanTotal {
+                    name: line_data.name.clone(),
+                    num_kids: spouse_line.data.num_kids,
+                    num_kids_span: range_to_span(
+                        spouse_line.line_num,
+                        &spouse_line.data.num_kids_range,
+                    ),
+                    total: line_data.num_kids,
+                    total_span: make_span(&line_data.num_kids_range),
+                });
+            }
+
             existing_data.henry_number = Some(line_data.primary_henry_number.clone());
+
+            // The counts on the primary line cover all of the person's partnerships,
+            // so they take precedence over whatever a spouse line declared.
+            existing_data.num_kids = line_data.num_kids;
+            existing_data.num_spouses = line_data.num_spouses;
+        } else if existing_data.henry_number.is_some()
+            && line_data.num_kids > existing_data.num_kids
+        {
+            return Err(ParseErrorKind::MoreKidsThanTotal {
+                name: line_data.name.clone(),
+                num_kids: line_data.num_kids,
+                num_kids_span: make_span(&line_data.num_kids_range),
+                total: existing_data.num_kids,
+                total_span: other_span(&|l| l.spousal_index.is_primary(), &|l| &l.num_kids_range),
+            });
         }
 
-        if line_data.comments != existing_data.comments {
-            if !line_data.comments.is_empty() {
-                if !existing_data.comments.is_empty() {
-                    return Err(ParseErrorKind::DifferentComments {
-                        name: line_data.name.clone(),
-                        name_span: range_to_span(line_num, &line_data.name_range),
-                        comments_span: range_to_span(line_num, &line_data.comments_range),
-                        other_span: existing_data.span,
-                    });
-                }
+        if line_data.comments != existing_data.comments && !line_data.comments.is_empty() {
+            if !existing_data.comments.is_empty() {
+                return Err(ParseErrorKind::DifferentComments {
+                    name: line_data.name.clone(),
+                    name_span: make_span(&line_data.name_range),
+                    comments_span: make_span(line_data.comments_range.as_ref().unwrap()),
+                    other_span: other_span(&|l| l.comments == existing_data.comments, &|l| {
+                        l.comments_range.as_ref().unwrap_or(&l.name_range)
+                    }),
+                });
+            }
+
+            existing_data.comments = line_data.comments.clone();
+            existing_data.blank_comments = false;
+        }
 
-                existing_data.comments = line_data.comments.clone();
+        if line_data.private_comments != existing_data.private_comments
+            && !line_data.private_comments.is_empty()
+        {
+            if !existing_data.private_comments.is_empty() {
+                return Err(ParseErrorKind::DifferentPrivateComments {
+                    name: line_data.name.clone(),
+                    name_span: make_span(&line_data.name_range),
+                    comments_span: make_span(line_data.private_comments_range.as_ref().unwrap()),
+                    other_span: other_span(
+                        &|l| l.private_comments == existing_data.private_comments,
+                        &|l| l.private_comments_range.as_ref().unwrap_or(&l.name_range),
+                    ),
+                });
             }
+
+            existing_data.private_comments = line_data.private_comments.clone();
         }
 
         Ok(())
     }
 }
 
+/// Number of single character insertions, deletions or substitutions to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Puts person lines into the depth-first order that the parser expects, using only their
+/// henry numbers and spousal indices. Each spouse line goes with a primary line that has the
+/// same henry number and each child with a primary line that has their parent's henry number:
+/// the closest such line before them in the file, or else the first one after them.
+/// Spouses and children that go with the same line keep their order from the file, so a
+/// child is attached to whichever spouse most recently precedes them.
+///
+/// Lines that go with nobody (root ancestors, but also lines whose parent or partner is
+/// missing) are kept at the top level, where the parser will report any problems.
+fn depth_first_order(lines: Vec<(usize, LineData)>) -> Vec<(usize, LineData)> {
+    let mut primary_lines: BTreeMap<&HenryNumber, Vec<usize>> = BTreeMap::new();
+    for (index, (_, line_data)) in lines.iter().enumerate() {
+        if line_data.spousal_index.is_primary() {
+            primary_lines
+                .entry(&line_data.primary_henry_number)
+                .or_default()
+                .push(index);
+        }
+    }
+
+    let mut top_level = vec![];
+    let mut attached: Vec<Vec<usize>> = vec![vec![]; lines.len()];
+    for (index, (_, line_data)) in lines.iter().enumerate() {
+        let hn = &line_data.primary_henry_number;
+        let candidates = if line_data.spousal_index.is_secondary() {
+            primary_lines.get(hn)
+        } else {
+            hn.parent()
+                .and_then(|parent_hn| primary_lines.get(&parent_hn))
+        };
+
+        let owner = candidates.and_then(|candidates| {
+            candidates
+                .iter()
+                .rev()
+                .find(|&&c| c < index)
+                .or(candidates.first())
+        });
+
+        match owner {
+            Some(&owner) => attached[owner].push(index),
+            None => top_level.push(index),
+        }
+    }
+
+    // Root ancestors and children are listed by henry number, children each after
+    // the spouse they are listed under.
+    let hn = |index: usize| &lines[index].1.primary_henry_number;
+    let is_child = |index: usize| lines[index].1.spousal_index.is_primary();
+    top_level.sort_by(|&a, &b| hn(a).cmp(hn(b)));
+    for indices in &mut attached {
+        for children in indices.chunk_by_mut(|&a, &b| is_child(a) && is_child(b)) {
+            children.sort_by(|&a, &b| hn(a).cmp(hn(b)));
+        }
+    }
+
+    fn visit(index: usize, attached: &[Vec<usize>], order: &mut Vec<usize>) {
+        order.push(index);
+        for &a in &attached[index] {
+            visit(a, attached, order);
+        }
+    }
+
+    let mut order = vec![];
+    for index in top_level {
+        visit(index, &attached, &mut order);
+    }
+
+    let mut lines: Vec<Option<(usize, LineData)>> = lines.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .map(|index| lines[index].take().unwrap())
+        .collect()
+}
+
 /// The data found on a line in the genea file
 #[derive(Debug)]
 struct LineData {
     primary_henry_number: HenryNumber,
     primary_henry_number_range: std::ops::Range<usize>,
     gender: Gender,
+    gender_range: std::ops::Range<usize>,
     num_kids: usize,
+    num_kids_range: std::ops::Range<usize>,
     num_spouses: usize,
+    num_spouses_range: std::ops::Range<usize>,
     spousal_index: SpousalIndex,
     spousal_index_range: std::ops::Range<usize>,
     secondary_henry_number: Option<HenryNumber>,
     secondary_henry_number_range: Option<std::ops::Range<usize>>,
     name: String,
     name_range: std::ops::Range<usize>,
+    aliases: Vec<String>,
+    persistent_id: Option<String>,
+    persistent_id_range: Option<std::ops::Range<usize>>,
     comments: String,
-    comments_range: std::ops::Range<usize>,
+
+    /// `None` if there is no `\` on the line; the comments may still be empty if there is
+    comments_range: Option<std::ops::Range<usize>>,
     private_comments: String,
+    private_comments_range: Option<std::ops::Range<usize>>,
+
+    /// In the indented layout, the indentation before the name
+    indent_range: Option<std::ops::Range<usize>>,
 }
 
-impl FromStr for LineData {
-    type Err = ParseErrorKind;
+impl LineData {
+    /// The name followed by any aliases
+    fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(self.aliases.iter().map(|a| a.as_str()))
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let Some(captures) = PERSON_LINE.captures(s) else {
+    fn parse(s: &str, layout: Layout) -> Result<Self, ParseErrorKind> {
+        // Whitespace at the end of the line is not part of the name or the (private) comments.
+        let s = s.trim_end();
+        let Some(captures) = layout.regex().captures(s) else {
             return Err(ParseErrorKind::MalformedLine);
         };
 
@@ -407,18 +1487,22 @@ impl FromStr for LineData {
             .as_str()
             .parse()
             .context("invalid gender")?;
+        let gender_range: std::ops::Range<usize> = captures.name("gender").unwrap().range();
         let numkids: usize = captures
             .name("numkids")
             .unwrap()
             .as_str()
             .parse()
             .context("invalid number of kids")?;
+        let num_kids_range: std::ops::Range<usize> = captures.name("numkids").unwrap().range();
         let numspouses: usize = captures
             .name("numspouses")
             .unwrap()
             .as_str()
             .parse()
             .context("invalid number of spouses")?;
+        let num_spouses_range: std::ops::Range<usize> =
+            captures.name("numspouses").unwrap().range();
         let spousal_index: SpousalIndex = captures
             .name("spouse")
             .unwrap()
@@ -433,30 +1517,55 @@ impl FromStr for LineData {
             None => None,
         };
         let secondary_henry_number_range = captures.name("altid").map(|c| c.range());
-        let name = captures.name("name").unwrap().as_str();
-        let name_range: std::ops::Range<usize> = captures.name("name").unwrap().range();
-        let comments: &str = captures.name("comment").map(|c| c.as_str()).unwrap_or("");
-        let comments_range = captures
+        let persistent_id = captures.name("id").map(|c| c.as_str().to_string());
+        let persistent_id_range = captures.name("id").map(|c| c.range());
+
+        // The name may be followed by aliases, separated by `|`
+        let name_match = captures.name("name").unwrap();
+        let mut names = name_match.as_str().split('|');
+        let name = names.next().unwrap();
+        let aliases: Vec<String> = names
+            .map(|alias| alias.trim().to_string())
+            .filter(|alias| !alias.is_empty())
+            .collect();
+        let name = name.trim_end();
+        if name.is_empty() {
+            return Err(ParseErrorKind::MalformedLine);
+        }
+        let name_range: std::ops::Range<usize> =
+            name_match.start()..name_match.start() + name.len();
+        let comments: &str = captures
             .name("comment")
-            .map(|r| r.range())
-            .unwrap_or(name_range.clone());
+            .map(|c| c.as_str().trim_end())
+            .unwrap_or("");
+        let comments_range = captures.name("comment").map(|r| r.range());
         let private_comments: &str = captures.name("private").map(|c| c.as_str()).unwrap_or("");
+        let private_comments_range = captures.name("private").map(|c| c.range());
+        let indent_range = captures.name("indent").map(|c| c.range());
 
         Ok(LineData {
             name_range,
             primary_henry_number_range,
             gender,
+            gender_range,
             name: name.to_string(),
+            aliases,
+            persistent_id,
+            persistent_id_range,
             comments: comments.to_string(),
             comments_range,
             private_comments: private_comments.to_string(),
+            private_comments_range,
             num_spouses: numspouses,
+            num_spouses_range,
             num_kids: numkids,
+            num_kids_range,
             primary_henry_number,
             spousal_index,
             spousal_index_range,
             secondary_henry_number,
             secondary_henry_number_range,
+            indent_range,
         })
     }
 }
@@ -470,7 +1579,7 @@ impl FromStr for HenryNumber {
         let mut ancestry: Vec<usize> = vec![];
 
         let mut words = s.split_whitespace();
-        while let Some(s) = words.next() {
+        for s in words.by_ref() {
             let u = usize::from_str(s)?;
             if u == 0 {
                 break;
@@ -490,12 +1599,83 @@ impl FromStr for HenryNumber {
 }
 
 impl HenryNumber {
-    /// The "alt" str is a compact henry number with one digit per generation and no spaces.
-    /// It is used in `genea.doc` for when a person appears in more than one family line.
+    /// The "alt" str is a henry number used in `genea.doc` for when a person appears
+    /// in more than one family line. It comes in two forms:
+    ///
+    /// * compact, with one digit per generation and no spaces (e.g., `1121000`);
+    /// * dotted, with the number for each generation separated by `.` (e.g., `1.1.10.2`),
+    ///   which is needed once somebody is the 10th (or later) child.
+    ///
+    /// A compact str with a zero in the middle (e.g., `1102`) is ambiguous and rejected;
+    /// see [`HenryNumber::ambiguous_alt_str`]. One that ends in zeros is read with each
+    /// digit as a generation, though it could be otherwise (see
+    /// [`HenryNumber::other_alt_str_reading`]).
     fn from_alt_str(s: &str) -> anyhow::Result<Self> {
+        if s.contains('.') {
+            let hn = Self::from_str(&s.replace('.', " "))?;
+            if hn.ancestry.is_empty() {
+                anyhow::bail!("empty alternate henry number");
+            }
+            return Ok(hn);
+        }
+
+        if Self::ambiguous_alt_str(s).is_some() {
+            anyhow::bail!("ambiguous alternate henry number");
+        }
+
         let expanded: String = Itertools::intersperse(s.chars(), ' ').collect();
         Self::from_str(&expanded)
     }
+
+    /// If `s` is a compact alt str with a zero before the trailing zeros, returns
+    /// the most likely reading, where each zero belongs to the digit before it
+    /// (so `1102` is read as `1.10.2`).
+    pub(super) fn ambiguous_alt_str(s: &str) -> Option<HenryNumber> {
+        let significant = s.trim_end_matches('0');
+        if s.contains('.') || !significant[1.min(significant.len())..].contains('0') {
+            return None;
+        }
+
+        let mut ancestry: Vec<usize> = vec![];
+        for ch in significant.chars() {
+            let digit = ch.to_digit(10)? as usize;
+            match ancestry.last_mut() {
+                Some(last) if digit == 0 => *last = last.checked_mul(10)?,
+                _ => ancestry.push(digit),
+            }
+        }
+
+        Some(HenryNumber { ancestry })
+    }
+
+    /// If `s` is a compact alt str that ends in zeros, returns the other way of reading
+    /// it, where the last generation takes the first of the zeros (so `1110000`, read as
+    /// 1-1-1, could also be 1-1-10). Only the dotted form can tell the two apart.
+    pub(super) fn other_alt_str_reading(s: &str) -> Option<HenryNumber> {
+        if s.contains('.') || !s.ends_with('0') || Self::ambiguous_alt_str(s).is_some() {
+            return None;
+        }
+
+        let mut hn = Self::from_alt_str(s).ok()?;
+        let last = hn.ancestry.last_mut()?;
+        *last = last.checked_mul(10)?;
+        Some(hn)
+    }
+
+    /// The dotted form of this henry number, which can't be misread.
+    pub(super) fn to_dotted_alt_string(&self) -> String {
+        self.ancestry.iter().join(".")
+    }
+
+    /// The alternate form of this henry number, as written in `genea.doc`:
+    /// compact if every generation fits in one digit and dotted otherwise.
+    pub(super) fn to_alt_string(&self) -> String {
+        if self.ancestry.iter().all(|&n| n < 10) {
+            self.ancestry.iter().join("")
+        } else {
+            self.ancestry.iter().join(".")
+        }
+    }
 }
 
 impl FromStr for Gender {
@@ -0,0 +1,185 @@
+//! Laying out the person lines of a `genea.doc` file in aligned columns.
+
+use itertools::Itertools;
+
+use super::{parse_text, Layout, LineData, ParseOptions};
+
+#[cfg(test)]
+mod tests;
+
+/// A line of the file, as far as formatting is concerned.
+enum FormatLine<'t> {
+    /// A line of the preamble, or a blank line, which is kept exactly as written
+    Verbatim(&'t str),
+
+    /// A person line
+    Person(&'t str, Box<LineData>),
+}
+
+/// The widths of the columns, so that they line up across all the person lines.
+#[derive(Default)]
+struct Widths {
+    henry_number: Vec<usize>,
+    num_kids: usize,
+    num_spouses: usize,
+    spousal_index: usize,
+    alt_id: usize,
+
+    /// In the indented layout, the indentation, persistent id and name
+    name: usize,
+}
+
+/// Returns `text` with every person line laid out in columns: the henry numbers (padded
+/// with zeros to the same number of generations), gender, counts, spousal index and
+/// alternate henry number are aligned, and whitespace around the name, comments and
+/// private comments is tidied up. The preamble is kept as it is.
+///
+/// Fails with the parse errors if the file is not valid.
+pub fn format_text(origin: impl std::fmt::Display, text: &str) -> anyhow::Result<String> {
+    parse_text(origin, text, ParseOptions::default())?;
+
+    let mut layout = None;
+    let mut lines = vec![];
+    for line in text.lines() {
+        let line_layout = match layout {
+            Some(layout) => layout,
+            None => match Layout::detect(line) {
+                Some(l) => *layout.insert(l),
+                None => {
+                    lines.push(FormatLine::Verbatim(line));
+                    continue;
+                }
+            },
+        };
+
+        if line.trim().is_empty() {
+            lines.push(FormatLine::Verbatim(line));
+            continue;
+        }
+
+        // We parsed the file successfully above, so every line after the preamble is a person line.
+        let line_data = LineData::parse(line, line_layout)?;
+        lines.push(FormatLine::Person(line, Box::new(line_data)));
+    }
+
+    let Some(layout) = layout else {
+        return Ok(text.to_string());
+    };
+
+    let mut widths = Widths::default();
+    for line in &lines {
+        let FormatLine::Person(line, line_data) = line else {
+            continue;
+        };
+
+        let columns: Vec<&str> = line[line_data.primary_henry_number_range.clone()]
+            .split_whitespace()
+            .collect();
+        if widths.henry_number.len() < columns.len() {
+            widths.henry_number.resize(columns.len(), 1);
+        }
+        for (width, column) in widths.henry_number.iter_mut().zip(columns) {
+            *width = (*width).max(column.len());
+        }
+
+        widths.num_kids = widths.num_kids.max(line_data.num_kids_range.len());
+        widths.num_spouses = widths.num_spouses.max(line_data.num_spouses_range.len());
+        widths.spousal_index = widths
+            .spousal_index
+            .max(line_data.spousal_index_range.len());
+        if let Some(range) = &line_data.secondary_henry_number_range {
+            widths.alt_id = widths.alt_id.max(range.len());
+        }
+        widths.name = widths.name.max(name_field(line, line_data).chars().count());
+    }
+
+    let mut output = String::new();
+    for line in &lines {
+        match line {
+            FormatLine::Verbatim(line) => output.push_str(line),
+            FormatLine::Person(line, line_data) => {
+                output.push_str(&format_person_line(layout, &widths, line, line_data))
+            }
+        }
+        output.push('\n');
+    }
+
+    if !text.ends_with('\n') {
+        output.pop();
+    }
+
+    Ok(output)
+}
+
+/// The indentation, persistent id, name and aliases of a person line.
+fn name_field(line: &str, line_data: &LineData) -> String {
+    let mut field = String::new();
+    if let Some(range) = &line_data.indent_range {
+        field.push_str(&line[range.clone()]);
+    }
+    if let Some(id) = &line_data.persistent_id {
+        field.push_str(&format!("#{id} "));
+    }
+    field.push_str(&line_data.names().map(|n| n.trim()).join("|"));
+    field
+}
+
+fn format_person_line(layout: Layout, widths: &Widths, line: &str, line_data: &LineData) -> String {
+    let mut columns = line[line_data.primary_henry_number_range.clone()].split_whitespace();
+    let mut numbers = String::new();
+    for &width in &widths.henry_number {
+        let column = columns.next().unwrap_or("0");
+        numbers.push_str(&format!(" {column:>width$}"));
+    }
+
+    numbers.push_str(&format!(
+        " {gender} {num_kids:>kids_width$} {num_spouses:>spouses_width$} {spousal_index:>index_width$}",
+        gender = &line[line_data.gender_range.clone()],
+        num_kids = &line[line_data.num_kids_range.clone()],
+        num_spouses = &line[line_data.num_spouses_range.clone()],
+        spousal_index = &line[line_data.spousal_index_range.clone()],
+        kids_width = widths.num_kids,
+        spouses_width = widths.num_spouses,
+        index_width = widths.spousal_index,
+    ));
+
+    let alt_id = match &line_data.secondary_henry_number_range {
+        Some(range) => &line[range.clone()],
+        None => "",
+    };
+
+    let name = name_field(line, line_data);
+    let mut output = match layout {
+        Layout::HenryFirst => {
+            let mut output = numbers;
+            if widths.alt_id > 0 {
+                output.push_str(&format!(" {alt_id:<width$}", width = widths.alt_id));
+            }
+            output.push(' ');
+            output.push_str(&name);
+            output
+        }
+        Layout::Indented => {
+            let padding = widths.name - name.chars().count();
+            let mut output = format!("{name}{:padding$}{numbers}", "");
+            if !alt_id.is_empty() {
+                output.push(' ');
+                output.push_str(alt_id);
+            }
+            output
+        }
+    };
+
+    // A `\` with nothing after it is kept, as it may mark a comment still to be written.
+    if line_data.comments_range.is_some() {
+        output.push('\\');
+        output.push_str(line_data.comments.trim_end());
+    }
+
+    if line_data.private_comments_range.is_some() {
+        output.push(';');
+        output.push_str(&line_data.private_comments);
+    }
+
+    output.trim_end().to_string()
+}
@@ -0,0 +1,71 @@
+//! How `family-tree fmt` lays out small `genea.doc` files.
+
+use super::format_text;
+use crate::genea::testing::GENEA_DOC;
+
+fn format(text: &str) -> String {
+    format_text("genea.doc", text).unwrap_or_else(|e| panic!("{e}\nin:\n{text}"))
+}
+
+#[test]
+fn genea_doc_is_already_formatted() {
+    assert_eq!(format(GENEA_DOC), GENEA_DOC);
+}
+
+#[test]
+fn formatting_twice_changes_nothing_more() {
+    let text =
+        " 1 M  1 1 0 Sarek\n 1 F 1 0 1 Amanda \\ Human\n 1  1 M 0 0 0 Spock\n 2 F 0 0 0 T'Pring\n";
+    let formatted = format(text);
+    assert_eq!(format(&formatted), formatted);
+}
+
+#[test]
+fn henry_numbers_are_padded_to_the_same_number_of_generations() {
+    assert_eq!(
+        format(" 1 M 1 0 0 Sarek\n 1 1 M 1 0 0 Spock\n 1 1 1 M 0 0 0 Sybok\n"),
+        " 1 0 0 M 1 0 0 Sarek\n 1 1 0 M 1 0 0 Spock\n 1 1 1 M 0 0 0 Sybok\n",
+    );
+}
+
+#[test]
+fn columns_are_aligned() {
+    assert_eq!(
+        format(
+            " 1 0 M 1 1 0   Sarek\n 1 0  F 1 0 1 21 Amanda Grayson \\ Human\n \
+             1 1 M 0 0 0 Spock ;private\n 2 0 F 1 0 0 Rose\n 2 1 F 1 0 0 Amanda Grayson\n"
+        ),
+        " 1 0 M 1 1 0    Sarek\n 1 0 F 1 0 1 21 Amanda Grayson\\ Human\n \
+         1 1 M 0 0 0    Spock;private\n 2 0 F 1 0 0    Rose\n 2 1 F 1 0 0    Amanda Grayson\n",
+    );
+}
+
+#[test]
+fn empty_comments_keep_their_backslash() {
+    let text = " 1 M 0 0 0 T'Amanda\\\n";
+    assert_eq!(format(text), text);
+}
+
+#[test]
+fn preamble_blank_lines_and_missing_final_newline_are_kept() {
+    let text = "A tree  \n\n 1 M 1 0 0 Sarek\n\n 1 1 M 0 0 0 Spock\n ";
+    assert_eq!(
+        format(text),
+        "A tree  \n\n 1 0 M 1 0 0 Sarek\n\n 1 1 M 0 0 0 Spock\n ",
+    );
+}
+
+#[test]
+fn indented_layout_aligns_the_numbers_after_the_names() {
+    assert_eq!(
+        format(
+            "Skon 1 0 0 M 1 0 0\n  Sarek 1 1 0 M 1 0 0 \\ ambassador\n    Spock 1 1 1 M 0 0 0\n"
+        ),
+        "Skon      1 0 0 M 1 0 0\n  Sarek   1 1 0 M 1 0 0\\ ambassador\n    Spock 1 1 1 M 0 0 0\n",
+    );
+}
+
+#[test]
+fn invalid_files_are_not_formatted() {
+    assert!(format_text("genea.doc", " 1 0 M 0 0 0 Sarek\n 1 0 M 0 0 0 Spock\n").is_err());
+}
@@ -0,0 +1,778 @@
+//! What the parser makes of small `genea.doc` files and the problems it reports in them.
+//! Whatever is in the file, parsing it should end in a tree or in diagnostics, never in a
+//! panic, which the last tests check by feeding the parser mangled copies of `genea.doc`.
+
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::Arc,
+};
+
+use super::{edit_distance, parse_text, Layout, LineData, ParseOptions, Parser};
+use crate::genea::{
+    error::{AnnotationKind, ParseError, ParseErrorKind, ParseErrors},
+    fix, normalize_name,
+    source_map::SourceMap,
+    testing::{mutate, random_genea_doc, Rng, GENEA_DOC},
+    Gender, Genea, SpousalIndex,
+};
+
+fn parse(text: &str) -> Genea {
+    parse_text("genea.doc", text, ParseOptions::default())
+        .unwrap_or_else(|e| panic!("{e}\nin:\n{text}"))
+}
+
+/// The problems that stop `text` from being parsed.
+fn errors(text: &str) -> ParseErrors {
+    errors_with(text, ParseOptions::default())
+}
+
+fn errors_with(text: &str, options: ParseOptions) -> ParseErrors {
+    match parse_text("genea.doc", text, options) {
+        Ok(_) => panic!("expected errors in:\n{text}"),
+        Err(e) => e.downcast().unwrap(),
+    }
+}
+
+#[test]
+fn matching_counts_give_no_warnings() {
+    let genea = parse(
+        " 1 0 M 2 1 0 Sarek\n 1 0 F 2 0 1 Amanda\n 1 1 M 0 0 0 Spock\n 1 2 F 0 0 0 Michael\n",
+    );
+    assert!(genea.warnings().is_empty());
+}
+
+#[test]
+fn wrong_number_of_kids_is_a_warning() {
+    let genea = parse(" 1 0 M 2 1 0 Sarek\n 1 0 F 1 0 1 Amanda\n 1 1 M 0 0 0 Spock\n");
+    let [warning] = genea.warnings() else {
+        panic!("expected one warning, got {:?}", genea.warnings());
+    };
+    assert_eq!(warning.line_num, 1);
+    let ParseErrorKind::WrongNumberOfKids {
+        name,
+        num_kids,
+        child_names,
+        ..
+    } = &warning.kind
+    else {
+        panic!("expected the wrong number of kids, got {:?}", warning.kind);
+    };
+    assert_eq!((name.as_str(), *num_kids), ("Sarek", 2));
+    assert_eq!(child_names, &["Spock"]);
+}
+
+#[test]
+fn spouse_lines_count_the_kids_of_that_partnership() {
+    let genea =
+        parse(" 1 0 M 2 2 0 Sarek\n 1 0 F 0 0 1 T'Rea\n 1 0 F 2 0 2 Amanda\n 1 1 M 0 0 0 Spock\n");
+    let names: Vec<&str> = genea
+        .warnings()
+        .iter()
+        .map(|w| match &w.kind {
+            ParseErrorKind::WrongNumberOfKids { name, .. } => name.as_str(),
+            kind => panic!("expected the wrong number of kids, got {kind:?}"),
+        })
+        .collect();
+    assert_eq!(names, ["Sarek", "Amanda"]);
+}
+
+#[test]
+fn wrong_number_of_spouses_is_a_warning() {
+    let genea = parse(" 1 0 M 0 2 0 Sarek\n 1 0 F 0 0 1 Amanda\n");
+    let [warning] = genea.warnings() else {
+        panic!("expected one warning, got {:?}", genea.warnings());
+    };
+    let ParseErrorKind::WrongNumberOfSpouses {
+        num_spouses,
+        spouse_names,
+        ..
+    } = &warning.kind
+    else {
+        panic!(
+            "expected the wrong number of spouses, got {:?}",
+            warning.kind
+        );
+    };
+    assert_eq!(*num_spouses, 2);
+    assert_eq!(spouse_names, &["Amanda"]);
+}
+
+#[test]
+fn every_malformed_line_is_reported() {
+    let errors = errors(" 1 0 M 2 0 0 Sarek\n 1 1 X 0 0 0 Spock\n 1 2 M 0 Sybok\n");
+    let lines: Vec<usize> = errors.errors.iter().map(|e| e.line_num).collect();
+    assert_eq!(lines, [2, 3]);
+}
+
+#[test]
+fn the_family_of_a_malformed_line_is_not_checked() {
+    // Neither Spock's count of kids nor his child, who would have nobody to be listed
+    // under, are reported: only the line that is wrong.
+    let errors = errors(" 1 0 M 1 0 0 Sarek\n 1 1 M one 0 0 Spock\n 1 1 1 M 0 0 0 Saavik\n");
+    let lines: Vec<usize> = errors.errors.iter().map(|e| e.line_num).collect();
+    assert_eq!(lines, [2]);
+}
+
+#[test]
+fn malformed_field_is_labelled_with_what_was_found() {
+    let errors = errors(" 1 0 M 1 0 0 Sarek\n 1 1 M x 0 0 Spock\n");
+    let [diagnostic] = &errors.diagnostics()[..] else {
+        panic!("expected one diagnostic, got {:?}", errors.diagnostics());
+    };
+    assert_eq!(
+        diagnostic.message,
+        "malformed number of kids, expected a number but found `x`"
+    );
+    assert_eq!(
+        diagnostic.annotations[0].label,
+        "found `x` where a number was expected"
+    );
+}
+
+#[test]
+fn diagnostics_serialize_with_their_severity_and_annotations() {
+    let errors = errors(" 1 0 M 1 1 0 Sarek\n 1 0 F 2 0 1 Amanda\n 1 1 M 0 0 0 Spock\n 1 1 1 X\n");
+    let diagnostics = serde_json::to_value(errors.diagnostics()).unwrap();
+    let summary: Vec<_> = diagnostics
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| {
+            (
+                d["severity"].as_str().unwrap(),
+                d["line"].as_u64().unwrap(),
+                d["annotations"][0]["kind"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [("warning", 2, "primary"), ("error", 4, "primary")]
+    );
+    assert_eq!(diagnostics[0]["file"], "genea.doc");
+    assert_eq!(
+        diagnostics[0]["annotations"][0]["span"],
+        serde_json::json!({ "line": 2, "start": 7, "end": 8 })
+    );
+}
+
+#[test]
+fn prose_starting_with_numbers_stays_in_the_preamble() {
+    let genea = parse("1 2 generations back, from the paper records\n 1 0 M 0 0 0 Skon\n");
+    let names: Vec<&str> = genea.people().map(|p| genea[p].name.as_str()).collect();
+    assert_eq!(names, ["Skon"]);
+}
+
+#[test]
+fn indented_layout_is_detected_from_the_first_person_line() {
+    let genea = parse(
+        "Printed from the paper records\n\
+         Skon 1 0 0 M 1 1 0\n\
+         Solkar's daughter 1 0 0 F 1 0 1\n  \
+         Sarek 1 1 0 M 1 0 0 \\ ambassador\n    \
+         Spock 1 1 1 M 0 0 0\n",
+    );
+    let names: Vec<&str> = genea.people().map(|p| genea[p].name.as_str()).collect();
+    assert_eq!(names, ["Skon", "Solkar's daughter", "Sarek", "Spock"]);
+    assert!(genea.warnings().is_empty());
+}
+
+#[test]
+fn indented_layout_checks_the_depth_of_each_generation() {
+    let errors = errors("Skon 1 0 0 M 1 0 0\n  Sarek 1 1 0 M 1 0 0\n   Spock 1 1 1 M 0 0 0\n");
+    let [error] = &errors.errors[..] else {
+        panic!("expected one error, got {:?}", errors.errors);
+    };
+    assert_eq!(error.line_num, 3);
+    let ParseErrorKind::WrongIndentation(payload) = &error.kind else {
+        panic!("expected the wrong indentation, got {:?}", error.kind);
+    };
+    assert_eq!((payload.expected, payload.found), (4, 3));
+}
+
+#[test]
+fn dotted_alt_id_can_name_a_tenth_child() {
+    let mut text = String::from(" 1 0 M 10 0 0 Sarek\n");
+    for i in 1..=10 {
+        text.push_str(&format!(" 1 {i} F 0 0 0 Child {i}\n"));
+    }
+    text.push_str(" 2 0 M 0 1 0 Stonn\n 2 0 F 0 0 1 1.10 Child 10\n");
+
+    let genea = parse(&text);
+    assert!(genea.warnings().is_empty());
+    assert_eq!(genea.people().count(), 12);
+}
+
+#[test]
+fn compact_alt_id_ending_in_zeros_is_a_warning() {
+    let genea = parse(
+        " 1 0 M 1 0 0 Sarek\n 1 1 F 0 0 0 T'Pau\n 2 0 M 0 1 0 Stonn\n 2 0 F 0 0 1 1100 T'Pau\n",
+    );
+    assert_eq!(genea.people().count(), 3);
+    let [warning] = genea.warnings() else {
+        panic!("expected one warning, got {:?}", genea.warnings());
+    };
+    assert_eq!(warning.line_num, 4);
+    let ParseErrorKind::AltIdEndsInZeros { hn, other_hn, .. } = &warning.kind else {
+        panic!("expected an alt id ending in zeros, got {:?}", warning.kind);
+    };
+    assert_eq!(hn.to_string(), "1-1");
+    assert_eq!(other_hn.to_string(), "1-10");
+    assert_eq!(
+        warning.diagnostic().annotations[1].label,
+        "Write it as `1.1` for 1-1, or as `1.10` for 1-10"
+    );
+}
+
+#[test]
+fn compact_alt_id_without_trailing_zeros_is_not_ambiguous() {
+    let genea = parse(
+        " 1 0 M 1 0 0 Sarek\n 1 1 F 0 0 0 T'Pau\n 2 0 M 0 1 0 Stonn\n 2 0 F 0 0 1 11 T'Pau\n",
+    );
+    assert!(genea.warnings().is_empty());
+}
+
+#[test]
+fn compact_alt_id_with_a_zero_in_the_middle_is_an_error() {
+    let errors = errors(" 1 0 M 0 1 0 Sarek\n 1 0 F 0 0 1 1021 T'Pau\n");
+    let [error] = &errors.errors[..] else {
+        panic!("expected one error, got {:?}", errors.errors);
+    };
+    assert!(
+        matches!(error.kind, ParseErrorKind::AmbiguousAltId { .. }),
+        "expected an ambiguous alt id, got {:?}",
+        error.kind
+    );
+}
+
+#[test]
+fn alt_id_can_refer_forward_in_the_file() {
+    let genea = parse(
+        " 1 0 M 0 1 0 Stonn\n 1 0 F 0 0 1 21 T'Pau\n 2 0 M 1 0 0 Sarek\n 2 1 F 0 0 0 T'Pau\n",
+    );
+    assert!(genea.warnings().is_empty());
+    assert_eq!(genea.people().count(), 3);
+
+    let t_pau = genea.people().find(|&p| genea[p].name == "T'Pau").unwrap();
+    assert_eq!(genea[t_pau].henry_number().unwrap().to_string(), "2-1");
+    assert!(genea[t_pau].child_in.is_some());
+    let [partnership] = genea[t_pau].parent_in[..] else {
+        panic!("expected T'Pau to have one partnership");
+    };
+    let partners: Vec<&str> = genea[partnership]
+        .parents
+        .iter()
+        .map(|&p| genea[p].name.as_str())
+        .collect();
+    assert_eq!(partners, ["Stonn", "T'Pau"]);
+}
+
+#[test]
+fn alt_id_that_nobody_has_is_an_error() {
+    let errors = errors(" 1 0 M 0 1 0 Stonn\n 1 0 F 0 0 1 31 T'Pau\n 2 0 M 0 0 0 Sarek\n");
+    let [error] = &errors.errors[..] else {
+        panic!("expected one error, got {:?}", errors.errors);
+    };
+    assert_eq!(error.line_num, 2);
+    assert!(
+        matches!(&error.kind, ParseErrorKind::UnresolvedAltId { hn, .. } if hn.to_string() == "3-1"),
+        "expected an unresolved alt id, got {:?}",
+        error.kind
+    );
+}
+
+#[test]
+fn alt_id_referring_forward_to_somebody_else_is_an_error() {
+    let errors = errors(
+        " 1 0 M 0 1 0 Stonn\n 1 0 F 0 0 1 21 T'Pau\n 2 0 M 1 0 0 Sarek\n 2 1 F 0 0 0 T'Pring\n",
+    );
+    let [error] = &errors.errors[..] else {
+        panic!("expected one error, got {:?}", errors.errors);
+    };
+    let ParseErrorKind::NoMatchingPerson(payload) = &error.kind else {
+        panic!("expected no matching person, got {:?}", error.kind);
+    };
+    assert_eq!(payload.name, "T'Pau");
+    assert_eq!(payload.existing_names, ["T'Pring"]);
+}
+
+#[test]
+fn lines_can_come_in_any_order() {
+    let text = " 1 0 M 2 0 0 Skon\n 1 1 M 1 0 0 Sarek\n 1 2 M 0 0 0 Sybok\n 1 1 1 M 0 0 0 Spock\n";
+    let genea = parse(text);
+    assert_eq!(genea.people().count(), 4);
+    assert!(genea.warnings().is_empty());
+
+    let spock = genea.people().find(|&p| genea[p].name == "Spock").unwrap();
+    let parents: Vec<&str> = genea[genea[spock].child_in.unwrap()]
+        .parents
+        .iter()
+        .map(|&p| genea[p].name.as_str())
+        .collect();
+    assert_eq!(parents, ["Sarek"]);
+}
+
+#[test]
+fn strict_order_reports_lines_out_of_order() {
+    let text = " 1 0 M 2 0 0 Skon\n 1 1 M 1 0 0 Sarek\n 1 2 M 0 0 0 Sybok\n 1 1 1 M 0 0 0 Spock\n";
+    let errors = errors_with(text, ParseOptions { strict_order: true });
+    assert!(
+        errors
+            .errors
+            .iter()
+            .any(|e| e.line_num == 4 && matches!(e.kind, ParseErrorKind::TopNotParent(_))),
+        "expected Spock to be reported as out of order, got {:?}",
+        errors.errors
+    );
+}
+
+#[test]
+fn children_are_ordered_by_henry_number() {
+    let genea = parse(" 1 0 M 2 0 0 A\n 1 2 F 0 0 0 C\n 1 1 M 0 0 0 B\n");
+    let a = genea.root_people().next().unwrap();
+    let children: Vec<&str> = genea[a]
+        .parent_in
+        .iter()
+        .flat_map(|&p| &genea[p].children)
+        .map(|&c| genea[c].name.as_str())
+        .collect();
+    assert_eq!(children, ["B", "C"]);
+}
+
+#[test]
+fn duplicate_root_ancestors_are_found_in_any_order() {
+    let errors = errors(" 2 0 M 0 0 0 Soval\n 1 0 F 0 0 0 Stonn\n 2 0 M 0 0 0 Soval\n");
+    assert!(
+        errors
+            .errors
+            .iter()
+            .any(|e| matches!(e.kind, ParseErrorKind::DuplicateRootAncestor(_))),
+        "expected a duplicate root ancestor, got {:?}",
+        errors.errors
+    );
+}
+
+/// Amanda is listed as Sarek's spouse on line 2, and under her own parents on line 4.
+fn amanda_lines(spouse_line: &str, primary_line: &str) -> String {
+    format!(" 1 0 M 0 1 0 Sarek\n{spouse_line}\n 2 0 M 1 0 0 Father\n{primary_line}\n")
+}
+
+#[test]
+fn known_gender_replaces_unknown_when_merging() {
+    let genea = parse(&amanda_lines(
+        " 1 0 ? 0 0 1 21 Amanda",
+        " 2 1 F 0 0 0 Amanda",
+    ));
+    let amanda = genea.people().find(|&p| genea[p].name == "Amanda").unwrap();
+    assert_eq!(genea[amanda].gender, Gender::Female);
+}
+
+#[test]
+fn different_genders_point_to_both_lines() {
+    let errors = errors(&amanda_lines(
+        " 1 0 F 0 0 1 21 Amanda",
+        " 2 1 M 0 0 0 Amanda",
+    ));
+    let [error] = &errors.errors[..] else {
+        panic!("expected one error, got {:?}", errors.errors);
+    };
+    let ParseErrorKind::DifferentGender {
+        gender_span,
+        other_span,
+        ..
+    } = &error.kind
+    else {
+        panic!("expected different genders, got {:?}", error.kind);
+    };
+    assert_eq!(
+        (gender_span.line_num, other_span.line_num),
+        (error.line_num, 2)
+    );
+}
+
+#[test]
+fn spouse_line_cannot_have_more_kids_than_the_primary_line() {
+    let text = amanda_lines(" 1 0 F 1 0 1 21 Amanda", " 2 1 F 0 0 0 Amanda")
+        .replace("0 1 0 Sarek", "1 1 0 Sarek")
+        + " 1 1 M 0 0 0 Spock\n";
+    let errors = errors(&text);
+    assert!(
+        errors.errors.iter().any(|e| matches!(
+            e.kind,
+            ParseErrorKind::MoreKidsThanTotal {
+                num_kids: 1,
+                total: 0,
+                ..
+            }
+        )),
+        "expected too many kids, got {:?}",
+        errors.errors
+    );
+}
+
+#[test]
+fn comments_may_be_given_on_just_one_line() {
+    let genea = parse(&amanda_lines(
+        " 1 0 F 0 0 1 21 Amanda",
+        " 2 1 F 0 0 0 Amanda\\Teacher",
+    ));
+    let amanda = genea.people().find(|&p| genea[p].name == "Amanda").unwrap();
+    assert_eq!(genea[amanda].comments, "Teacher");
+
+    let errors = errors(&amanda_lines(
+        " 1 0 F 0 0 1 21 Amanda\\Linguist",
+        " 2 1 F 0 0 0 Amanda\\Teacher",
+    ));
+    assert!(
+        matches!(
+            &errors.errors[..],
+            [ParseError {
+                kind: ParseErrorKind::DifferentComments { .. },
+                ..
+            }]
+        ),
+        "expected different comments, got {:?}",
+        errors.errors
+    );
+}
+
+#[test]
+fn mismatched_name_points_to_both_lines() {
+    // Names are matched before lines are merged, so this can't come out of parsing a file.
+    let text = amanda_lines(" 1 0 F 0 0 1 21 Amanda", " 2 1 F 0 0 0 Amanda");
+    let mut genea = parse(&text);
+    let amanda = genea.people().find(|&p| genea[p].name == "Amanda").unwrap();
+    let line_data = LineData::parse(" 2 1 F 0 0 0 Amanda Grayson", Layout::HenryFirst).unwrap();
+
+    let kind = Parser::merge_person(5, &mut genea[amanda], &[], &line_data).unwrap_err();
+    let error = ParseError {
+        source_map: Arc::new(SourceMap::new(
+            "genea.doc",
+            &(text + " 2 1 F 0 0 0 Amanda Grayson\n"),
+        )),
+        line_num: 5,
+        kind,
+    };
+    let annotations: Vec<_> = error
+        .diagnostic()
+        .annotations
+        .into_iter()
+        .map(|a| (a.kind, a.span.line_num))
+        .collect();
+    assert_eq!(
+        annotations,
+        [(AnnotationKind::Secondary, 2), (AnnotationKind::Primary, 5)]
+    );
+}
+
+#[test]
+fn edit_distance_counts_single_character_changes() {
+    assert_eq!(edit_distance("Amanda", "Amanda"), 0);
+    assert_eq!(edit_distance("Amanda", "Amandah"), 1);
+    assert_eq!(edit_distance("Amanda", "Amenda"), 1);
+    assert_eq!(edit_distance("Sarek", "Surak"), 2);
+    assert_eq!(edit_distance("", "T'Pau"), 5);
+}
+
+#[test]
+fn aliases_are_accepted_when_matching_names() {
+    let genea = parse(&amanda_lines(
+        " 1 0 F 0 0 1 21 Amanda",
+        " 2 1 F 0 0 0 Amanda Grayson | Amanda",
+    ));
+    assert_eq!(genea.people().count(), 3);
+    let amanda = genea
+        .people()
+        .find(|&p| genea[p].is_named("Amanda"))
+        .unwrap();
+    // The first line's name is kept, and the other spelling becomes an alias.
+    assert_eq!(genea[amanda].name, "Amanda");
+    assert_eq!(genea[amanda].aliases, ["Amanda Grayson"]);
+}
+
+#[test]
+fn close_name_is_suggested() {
+    let errors = errors(&amanda_lines(
+        " 1 0 F 0 0 1 21 Amanda",
+        " 2 1 F 0 0 0 Amandah",
+    ));
+    let [error] = &errors.errors[..] else {
+        panic!("expected one error, got {:?}", errors.errors);
+    };
+    let ParseErrorKind::NoMatchingPerson(payload) = &error.kind else {
+        panic!("expected no matching person, got {:?}", error.kind);
+    };
+    assert_eq!(payload.suggestion.as_deref(), Some("Amandah"));
+    assert!(error
+        .diagnostic()
+        .annotations
+        .iter()
+        .any(|a| a.kind == AnnotationKind::Help && a.label.contains("`Amanda|Amandah`")));
+}
+
+#[test]
+fn distant_name_is_not_suggested() {
+    let errors = errors(&amanda_lines(
+        " 1 0 F 0 0 1 21 Amanda",
+        " 2 1 F 0 0 0 T'Pring",
+    ));
+    let ParseErrorKind::NoMatchingPerson(payload) = &errors.errors[0].kind else {
+        panic!("expected no matching person, got {:?}", errors.errors);
+    };
+    assert_eq!(payload.suggestion, None);
+}
+
+#[test]
+fn names_are_normalized_for_comparison() {
+    // "é" written as one code point, and as "e" followed by a combining accent.
+    assert_eq!(normalize_name("Andr\u{e9}"), normalize_name("Andre\u{301}"));
+    assert_eq!(normalize_name("T\u{2019}Melis"), "T'Melis");
+    assert_eq!(normalize_name(" Amanda   Grayson "), "Amanda Grayson");
+    assert_ne!(normalize_name("Amanda"), normalize_name("amanda"));
+}
+
+#[test]
+fn look_alike_names_match_with_a_warning() {
+    let genea = parse(&amanda_lines(
+        " 1 0 F 0 0 1 21 T'Pau",
+        " 2 1 F 0 0 0 T\u{2019}Pau",
+    ));
+    assert_eq!(genea.people().count(), 3);
+
+    let [warning] = genea.warnings() else {
+        panic!("expected one warning, got {:?}", genea.warnings());
+    };
+    assert_eq!(warning.line_num, 4);
+    let ParseErrorKind::SimilarNames {
+        name,
+        other_name,
+        other_span,
+        ..
+    } = &warning.kind
+    else {
+        panic!("expected similar names, got {:?}", warning.kind);
+    };
+    assert_eq!(
+        (name.as_str(), other_name.as_str()),
+        ("T\u{2019}Pau", "T'Pau")
+    );
+    assert_eq!(other_span.line_num, 2);
+}
+
+#[test]
+fn spouse_of_themselves_is_an_error() {
+    let errors = errors(" 1 0 M 0 1 0 Sarek\n 1 0 M 0 0 1 1 Sarek\n");
+    let [error] = &errors.errors[..] else {
+        panic!("expected one error, got {:?}", errors.errors);
+    };
+    let ParseErrorKind::SelfPartnership {
+        name_span,
+        spouse_span,
+        ..
+    } = &error.kind
+    else {
+        panic!("expected a self-partnership, got {:?}", error.kind);
+    };
+    assert_eq!((name_span.line_num, spouse_span.line_num), (1, 2));
+}
+
+#[test]
+fn marrying_a_child_is_reported_every_way_it_goes_wrong() {
+    // Sarek's spouse line makes him the partner of his own father, and so the father of
+    // himself and of his brother Sybok.
+    let errors = errors(
+        " 1 0 M 2 1 0 Skon\n 1 0 M 2 0 1 11 Sarek\n 1 1 M 2 1 0 Sarek\n 1 2 M 0 0 0 Sybok\n",
+    );
+    let kinds: Vec<&ParseErrorKind> = errors.errors.iter().map(|e| &e.kind).collect();
+    assert!(
+        matches!(
+            &kinds[..],
+            [
+                ParseErrorKind::ChildOfPartner { partner_name, .. },
+                ParseErrorKind::ParentAndSibling { child_name, .. },
+                ParseErrorKind::AncestryCycle { ancestor_names, .. },
+                ..
+            ] if partner_name == "Skon" && child_name == "Sybok" && ancestor_names == &["Sarek"]
+        ),
+        "expected the impossible relationships to be reported, got {kinds:?}"
+    );
+    // Sarek was first read from his spouse line.
+    assert!(errors.errors[..3].iter().all(|e| e.line_num == 2));
+}
+
+#[test]
+fn spouses_keep_their_declared_index() {
+    let genea = parse(" 1 0 M 0 2 0 Sarek\n 1 0 F 0 0 1 T'Rea\n 1 0 F 0 0 2 Amanda\n");
+    let sarek = genea.root_people().next().unwrap();
+    let indices: Vec<Option<SpousalIndex>> = genea[sarek]
+        .parent_in
+        .iter()
+        .map(|&p| genea[p].spousal_index)
+        .collect();
+    assert_eq!(indices, [Some(SpousalIndex(1)), Some(SpousalIndex(2))]);
+}
+
+#[test]
+fn gap_in_spousal_indices_is_fixed() {
+    let text = " 1 0 M 0 2 0 Sarek\n 1 0 F 0 0 1 T'Rea\n 1 0 F 0 0 3 Amanda\n";
+    let errors = errors(text);
+    let [error] = &errors.errors[..] else {
+        panic!("expected one error, got {:?}", errors.errors);
+    };
+    assert_eq!(error.line_num, 3);
+    assert!(
+        matches!(&error.kind, ParseErrorKind::SpousalIndexOutOfSequence(payload) if payload.expected == 2),
+        "expected a spousal index out of sequence, got {:?}",
+        error.kind
+    );
+
+    let fixed = fix::apply_edits(text, &errors.fixes());
+    assert_eq!(
+        fixed.text,
+        " 1 0 M 0 2 0 Sarek\n 1 0 F 0 0 1 T'Rea\n 1 0 F 0 0 2 Amanda\n"
+    );
+}
+
+#[test]
+fn duplicate_spousal_index_is_an_error() {
+    let errors = errors(" 1 0 M 0 2 0 Sarek\n 1 0 F 0 0 1 T'Rea\n 1 0 F 0 0 1 Amanda\n");
+    let [error] = &errors.errors[..] else {
+        panic!("expected one error, got {:?}", errors.errors);
+    };
+    assert_eq!(error.line_num, 3);
+    assert!(
+        matches!(&error.kind, ParseErrorKind::SpousalIndexOutOfSequence(payload) if payload.expected == 2),
+        "expected a spousal index out of sequence, got {:?}",
+        error.kind
+    );
+}
+
+#[test]
+fn persistent_id_is_given_once_for_everybody_on_their_lines() {
+    let genea = parse(&amanda_lines(
+        " 1 0 F 0 0 1 21 #amanda Amanda",
+        " 2 1 F 0 0 0 Amanda",
+    ));
+    let amanda = genea.people().find(|&p| genea[p].name == "Amanda").unwrap();
+    assert_eq!(genea[amanda].persistent_id.as_deref(), Some("amanda"));
+    assert_eq!(genea.id(amanda), "amanda");
+}
+
+#[test]
+fn different_persistent_ids_for_one_person_is_an_error() {
+    let errors = errors(&amanda_lines(
+        " 1 0 F 0 0 1 21 #amanda Amanda",
+        " 2 1 F 0 0 0 #grayson Amanda",
+    ));
+    let [error] = &errors.errors[..] else {
+        panic!("expected one error, got {:?}", errors.errors);
+    };
+    let ParseErrorKind::DifferentPersistentIds(payload) = &error.kind else {
+        panic!("expected different persistent ids, got {:?}", error.kind);
+    };
+    assert_eq!(
+        (payload.id.as_str(), payload.other_id.as_str()),
+        ("grayson", "amanda")
+    );
+    assert_eq!(
+        (payload.id_span.line_num, payload.other_span.line_num),
+        (4, 2)
+    );
+}
+
+#[test]
+fn persistent_id_used_for_two_people_is_an_error() {
+    let errors =
+        errors(" 1 0 M 2 0 0 #skon Skon\n 1 1 M 0 0 0 #son Sarek\n 1 2 M 0 0 0 #son Sybok\n");
+    let [error] = &errors.errors[..] else {
+        panic!("expected one error, got {:?}", errors.errors);
+    };
+    assert_eq!(error.line_num, 3);
+    let ParseErrorKind::DuplicatePersistentId(payload) = &error.kind else {
+        panic!("expected a duplicate persistent id, got {:?}", error.kind);
+    };
+    assert_eq!(
+        (payload.name.as_str(), payload.other_name.as_str()),
+        ("Sybok", "Sarek")
+    );
+}
+
+#[test]
+fn duplicate_root_ancestor_suggests_the_next_free_henry_number() {
+    let errors = errors(" 1 M 0 0 0 Skon\n 2 M 0 0 0 Sarek\n 2 M 0 0 0 Solkar\n");
+    let [error] = &errors.errors[..] else {
+        panic!("expected one error, got {:?}", errors.errors);
+    };
+    let ParseErrorKind::DuplicateRootAncestor(payload) = &error.kind else {
+        panic!("expected a duplicate root ancestor, got {:?}", error.kind);
+    };
+    assert_eq!(payload.other_name, "Sarek");
+    assert_eq!(payload.suggested_hn, Some("3".parse().unwrap()));
+}
+
+/// Parses `text` with and without `--strict-order`, and renders whatever comes out of it:
+/// the diagnostics, the text with the suggested fixes applied and the regenerated file.
+fn parse_everything(text: &str) {
+    for strict_order in [false, true] {
+        match parse_text("genea.doc", text, ParseOptions { strict_order }) {
+            Ok(genea) => {
+                for warning in genea.warnings() {
+                    let _ = (warning.to_string(), warning.diagnostic());
+                }
+                let _ = genea.to_genea_doc();
+            }
+            Err(e) => {
+                let _ = e.to_string();
+                if let Some(errors) = e.downcast_ref::<ParseErrors>() {
+                    let _ = errors.diagnostics();
+                    let fixed = fix::apply_edits(text, &errors.fixes());
+                    let _ = parse_text("genea.doc", &fixed.text, ParseOptions { strict_order });
+                }
+            }
+        }
+    }
+}
+
+fn assert_no_panic(text: &str) {
+    if catch_unwind(AssertUnwindSafe(|| parse_everything(text))).is_err() {
+        panic!("parsing panicked on this input:\n{text}");
+    }
+}
+
+#[test]
+fn genea_doc_parses() {
+    parse(GENEA_DOC);
+}
+
+#[test]
+fn huge_henry_numbers() {
+    // Suggesting the next free henry number used to overflow.
+    assert_no_panic(
+        " 1 0 M 1 0 0 R\n 1 18446744073709551615 M 0 0 0 A\n 1 18446744073709551615 M 0 0 0 C\n",
+    );
+    assert_no_panic(" 18446744073709551615 M 0 0 0 A\n 18446744073709551615 M 0 0 0 C\n");
+    assert_no_panic(
+        " 1 0 M 2 0 0 R\n 1 18446744073709551615 M 0 0 0 A\n 1 18446744073709551615 M 0 0 0 B\n",
+    );
+}
+
+#[test]
+fn mutated_genea_doc_never_panics() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..500 {
+        let mut lines: Vec<String> = GENEA_DOC.lines().map(String::from).collect();
+        mutate(&mut rng, &mut lines);
+        assert_no_panic(&(lines.join("\n") + "\n"));
+    }
+}
+
+#[test]
+fn mutated_random_trees_never_panic() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    for _ in 0..200 {
+        let text = random_genea_doc(&mut rng);
+        let mut lines: Vec<String> = text.lines().map(String::from).collect();
+        mutate(&mut rng, &mut lines);
+        assert_no_panic(&(lines.join("\n") + "\n"));
+    }
+}
@@ -0,0 +1,64 @@
+use super::Span;
+
+/// The text that was parsed along with a name for where it came from
+/// (a file path, `<stdin>`, etc). Diagnostics hold on to this so they can
+/// render snippets without going back to the filesystem.
+#[derive(Debug)]
+pub struct SourceMap {
+    origin: String,
+    text: String,
+
+    /// The offset of the starting byte for each (zero-indexed) line
+    line_offsets: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new(origin: impl std::fmt::Display, text: &str) -> Self {
+        let line_offsets = std::iter::once(0)
+            .chain(
+                text.char_indices()
+                    .filter(|&(_, ch)| ch == '\n')
+                    .map(|(offset, _)| offset + 1),
+            )
+            .collect();
+
+        Self {
+            origin: origin.to_string(),
+            text: text.to_string(),
+            line_offsets,
+        }
+    }
+
+    /// Where the text came from, used when reporting errors
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Byte range of the given (1-indexed) line within the text, excluding the newline
+    pub fn line_range(&self, line_num: usize) -> std::ops::Range<usize> {
+        let line_index = line_num.saturating_sub(1).min(self.line_offsets.len() - 1);
+        let start = self.line_offsets[line_index];
+        let end = self
+            .line_offsets
+            .get(line_index + 1)
+            .map(|i| *i - 1)
+            .unwrap_or(self.text.len());
+        start..end
+    }
+
+    /// Byte range of the given span within the text
+    pub fn span_range(&self, span: Span) -> std::ops::Range<usize> {
+        let line_range = self.line_range(span.line_num);
+        match span.chars {
+            Some((start, end)) => {
+                (line_range.start + start).min(line_range.end)
+                    ..(line_range.start + end).min(line_range.end)
+            }
+            None => line_range,
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Helpers shared by the tests: the real `genea.doc` and random family trees.
+
+use super::HenryNumber;
+
+pub const GENEA_DOC: &str = include_str!("../../genea.doc");
+
+/// A small deterministic random number generator (xorshift), so that failures can be
+/// reproduced without depending on a random number crate.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    pub fn choose<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+/// Things to splice into lines: the pieces of a person line, plus some that don't belong.
+const TOKENS: &[&str] = &[
+    "0",
+    "1",
+    "2",
+    "10",
+    "18446744073709551615",
+    "18446744073709551616",
+    "99999999999999999999999",
+    "-1",
+    "M",
+    "F",
+    "?",
+    "X",
+    "\\",
+    ";",
+    "|",
+    "#",
+    "#id",
+    "1.2",
+    "1.0.3",
+    "1110000",
+    "2100000",
+    "0 0",
+    "  ",
+    "Spock",
+    "é",
+    "T’Pring",
+];
+
+/// Makes a few random changes to the lines: replacing or inserting a token, and
+/// duplicating, deleting or swapping whole lines.
+pub fn mutate(rng: &mut Rng, lines: &mut Vec<String>) {
+    for _ in 0..1 + rng.below(6) {
+        if lines.is_empty() {
+            return;
+        }
+
+        let i = rng.below(lines.len());
+        match rng.below(5) {
+            0 => {
+                let mut words: Vec<&str> = lines[i].split(' ').collect();
+                let j = rng.below(words.len());
+                words[j] = rng.choose(TOKENS);
+                lines[i] = words.join(" ");
+            }
+            1 => {
+                let line = lines[i].clone();
+                lines.insert(rng.below(lines.len() + 1), line);
+            }
+            2 => {
+                lines.remove(i);
+            }
+            3 => {
+                let j = rng.below(lines.len());
+                lines.swap(i, j);
+            }
+            _ => {
+                let boundaries: Vec<usize> = lines[i]
+                    .char_indices()
+                    .map(|(k, _)| k)
+                    .chain([lines[i].len()])
+                    .collect();
+                let k = boundaries[rng.below(boundaries.len())];
+                lines[i].insert_str(k, rng.choose(TOKENS));
+            }
+        }
+    }
+}
+
+const NAMES: &[&str] = &[
+    "Amanda", "Sarek", "Spock", "Sybok", "Skon", "Solkar", "T'Pau", "T'Pring", "Michael", "Stonn",
+    "Valeris", "Saavik", "Tuvok", "T'Pol", "Soval", "T'Les",
+];
+
+/// A person line of a generated file
+struct GeneratedLine {
+    hn: HenryNumber,
+    gender: &'static str,
+    num_kids: usize,
+    num_spouses: usize,
+    spousal_index: usize,
+    alt_hn: Option<HenryNumber>,
+    id: Option<String>,
+    names: Vec<String>,
+    comments: &'static str,
+    private_comments: &'static str,
+}
+
+struct Generator<'r> {
+    rng: &'r mut Rng,
+    lines: Vec<GeneratedLine>,
+    num_names: usize,
+}
+
+impl Generator<'_> {
+    fn name(&mut self) -> String {
+        self.num_names += 1;
+        format!("{} {}", self.rng.choose(NAMES), self.num_names)
+    }
+
+    fn line(&mut self, hn: &[usize], gender: &'static str, spousal_index: usize) -> usize {
+        let name = self.name();
+        let mut names = vec![name];
+        if self.rng.chance(10) {
+            names.push(self.name());
+        }
+
+        let line = GeneratedLine {
+            hn: HenryNumber {
+                ancestry: hn.to_vec(),
+            },
+            gender,
+            num_kids: 0,
+            num_spouses: 0,
+            spousal_index,
+            alt_hn: None,
+            id: self.rng.chance(10).then(|| format!("p{}", self.num_names)),
+            names,
+            comments: self.rng.choose(&["", "", "", "born on Vulcan"]),
+            private_comments: self.rng.choose(&["", "", "", "check the dates"]),
+        };
+        self.lines.push(line);
+        self.lines.len() - 1
+    }
+
+    /// Adds the line for a family member, followed by those of their spouses and children.
+    fn family_member(&mut self, hn: &mut Vec<usize>) {
+        let gender = self.rng.choose(&["M", "F", "?"]);
+        let index = self.line(hn, gender, 0);
+        if hn.len() >= 4 {
+            return;
+        }
+
+        let mut num_kids = 0;
+        let mut child = |generator: &mut Self, hn: &mut Vec<usize>| {
+            num_kids += 1;
+            hn.push(num_kids);
+            generator.family_member(hn);
+            hn.pop();
+        };
+
+        // Children listed without a spouse...
+        if self.rng.chance(25) {
+            child(self, hn);
+        }
+
+        // ...and then each spouse, followed by their children.
+        let num_spouses = self.rng.choose(&["0", "1", "1", "2"]).parse().unwrap();
+        for spousal_index in 1..=num_spouses {
+            let gender = self.rng.choose(&["M", "F"]);
+            let spouse = self.line(hn, gender, spousal_index);
+            let spouse_kids = self.rng.below(3);
+            self.lines[spouse].num_kids = spouse_kids;
+            for _ in 0..spouse_kids {
+                child(self, hn);
+            }
+        }
+
+        self.lines[index].num_kids = num_kids;
+        self.lines[index].num_spouses = num_spouses;
+    }
+
+    /// Makes some of the spouses family members from elsewhere in the tree, which are
+    /// linked to their spouse line by alternate henry numbers.
+    fn marry_within_family(&mut self) {
+        for spouse in 0..self.lines.len() {
+            if self.lines[spouse].spousal_index == 0 || !self.rng.chance(15) {
+                continue;
+            }
+
+            // Only members of a later family, so that nobody ends up their own ancestor.
+            let spouse_hn = &self.lines[spouse].hn;
+            let candidates: Vec<usize> = (0..self.lines.len())
+                .filter(|&i| {
+                    let line = &self.lines[i];
+                    line.spousal_index == 0
+                        && line.alt_hn.is_none()
+                        && line.hn.ancestry[0] > spouse_hn.ancestry[0]
+                })
+                .collect();
+            if candidates.is_empty() {
+                continue;
+            }
+
+            let member = candidates[self.rng.below(candidates.len())];
+            let gender = self.lines[spouse].gender;
+            let spouse_kids = self.lines[spouse].num_kids;
+            let spouse_hn = spouse_hn.clone();
+            let member_line = &mut self.lines[member];
+            member_line.gender = gender;
+            member_line.num_kids += spouse_kids;
+            member_line.alt_hn = Some(spouse_hn);
+            let member_hn = member_line.hn.clone();
+            let name = member_line.names[0].clone();
+
+            let spouse_line = &mut self.lines[spouse];
+            spouse_line.alt_hn = Some(member_hn);
+            spouse_line.names = vec![name];
+            spouse_line.id = None;
+            spouse_line.comments = "";
+            spouse_line.private_comments = "";
+        }
+    }
+
+    fn text(&self) -> String {
+        let mut text = String::from("A family tree made up for testing\n\n");
+        for line in &self.lines {
+            for column in 0..10 {
+                let n = line.hn.ancestry.get(column).copied().unwrap_or(0);
+                text.push_str(&format!(" {n}"));
+            }
+            text.push_str(&format!(
+                " {} {} {} {}",
+                line.gender, line.num_kids, line.num_spouses, line.spousal_index
+            ));
+            if let Some(alt_hn) = &line.alt_hn {
+                text.push_str(&format!(" {:0<7}", alt_hn.to_alt_string()));
+            }
+            if let Some(id) = &line.id {
+                text.push_str(&format!(" #{id}"));
+            }
+            text.push_str(&format!(" {}", line.names.join("|")));
+            if !line.comments.is_empty() {
+                text.push_str(&format!("\\{}", line.comments));
+            }
+            if !line.private_comments.is_empty() {
+                text.push_str(&format!(";{}", line.private_comments));
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// A random `genea.doc` file describing a valid tree of a few families, some of whose
+/// members marry each other.
+pub fn random_genea_doc(rng: &mut Rng) -> String {
+    let mut generator = Generator {
+        rng,
+        lines: vec![],
+        num_names: 0,
+    };
+
+    for root in 1..=1 + generator.rng.below(3) {
+        generator.family_member(&mut vec![root]);
+    }
+    generator.marry_within_family();
+    generator.text()
+}
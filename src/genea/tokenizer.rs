@@ -0,0 +1,350 @@
+//! A hand-written, field-by-field reading of a person line. The regular expression
+//! in the parser is the fast path; when it fails to match (or matches but the values
+//! don't make sense), we walk the line with this tokenizer to find the first field
+//! that is wrong and explain what we expected to find there.
+
+use super::{error::ParseErrorKind, parser::Layout, HenryNumber, Span};
+
+#[cfg(test)]
+mod tests;
+
+/// The fields of a person line, in the order they appear.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineField {
+    HenryNumber,
+    Gender,
+    NumKids,
+    NumSpouses,
+    SpousalIndex,
+    AltId,
+    PersistentId,
+    Name,
+}
+
+impl std::fmt::Display for LineField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LineField::HenryNumber => write!(f, "henry number"),
+            LineField::Gender => write!(f, "gender"),
+            LineField::NumKids => write!(f, "number of kids"),
+            LineField::NumSpouses => write!(f, "number of spouses"),
+            LineField::SpousalIndex => write!(f, "spousal index"),
+            LineField::AltId => write!(f, "alternate henry number"),
+            LineField::PersistentId => write!(f, "persistent id"),
+            LineField::Name => write!(f, "name"),
+        }
+    }
+}
+
+/// Does this line look like somebody tried to write a person line?
+/// Used to tell the end of the preamble apart from prose, so it asks for the start
+/// of a line in the henry-first layout: some numbers followed by a gender.
+pub fn looks_like_person_line(line: &str) -> bool {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let num_numbers = words.iter().take_while(|word| is_number(word)).count();
+    num_numbers > 0 && words.get(num_numbers).is_some_and(|word| is_gender(word))
+}
+
+/// Explains why `line` is not a valid person line in the given layout, or `None` if
+/// every field looks fine to the tokenizer.
+pub fn diagnose(line: &str, line_num: usize, layout: Layout) -> Option<ParseErrorKind> {
+    let mut tokenizer = Tokenizer {
+        line,
+        line_num,
+        layout,
+        pos: 0,
+    };
+
+    tokenizer.check_fields().err()
+}
+
+/// The henry number of a line that is not a valid person line, if its henry number
+/// columns can still be read. This lets us tell which family the line belonged to.
+pub fn henry_number(line: &str, layout: Layout) -> Option<HenryNumber> {
+    let mut tokenizer = Tokenizer {
+        line,
+        line_num: 0,
+        layout,
+        pos: 0,
+    };
+
+    tokenizer.leading_fields().ok()?;
+    let hn = tokenizer.henry_number().ok()?;
+
+    // Without a gender after them, the counts may have been read as part of the henry number.
+    tokenizer
+        .peek_token()
+        .is_some_and(|(_, token)| is_gender(token))
+        .then_some(hn)
+}
+
+struct Tokenizer<'l> {
+    line: &'l str,
+    line_num: usize,
+    layout: Layout,
+    pos: usize,
+}
+
+impl<'l> Tokenizer<'l> {
+    fn check_fields(&mut self) -> Result<(), ParseErrorKind> {
+        self.leading_fields()?;
+        self.henry_number()?;
+
+        // Gender: a single character, which may be run together with the number of kids.
+        match self.line[self.pos..].chars().next() {
+            Some('M' | 'F' | '?') => self.pos += 1,
+            _ => return Err(self.unexpected(LineField::Gender, "`M`, `F` or `?`")),
+        }
+        self.skip_whitespace();
+
+        self.expect_number(LineField::NumKids)?;
+        self.expect_number(LineField::NumSpouses)?;
+        self.expect_number(LineField::SpousalIndex)?;
+
+        // Alternate henry number: optional, either compact (one digit per generation
+        // and then zeros) or dotted (numbers separated by `.`)
+        if let Some((start, token)) = self.peek_token() {
+            if is_number(token) {
+                if let Some(suggested_hn) = HenryNumber::ambiguous_alt_str(token) {
+                    return Err(ParseErrorKind::AmbiguousAltId {
+                        alt_id: token.to_string(),
+                        alt_id_span: self.span(start, token),
+                        suggested_hn,
+                    });
+                }
+                self.advance_past(start, token);
+                self.skip_whitespace();
+            } else if token.starts_with(|c: char| c.is_ascii_digit()) && token.contains('.') {
+                if !is_dotted_alt_id(token) {
+                    return Err(self.error(
+                        LineField::AltId,
+                        start,
+                        token,
+                        "non-zero numbers separated by `.`, like `1.1.10.2`",
+                    ));
+                }
+                self.advance_past(start, token);
+                self.skip_whitespace();
+            }
+        }
+
+        if self.layout == Layout::HenryFirst {
+            self.persistent_id()?;
+        }
+
+        // Name (or, in the indented layout, nothing): anything up to the comments
+        let rest = &self.line[self.pos..];
+        let name_len = rest.find(['\\', ';']).unwrap_or(rest.len());
+        match self.layout {
+            Layout::HenryFirst => {
+                if rest[..name_len].trim().is_empty() {
+                    return Err(self.unexpected(LineField::Name, "a name"));
+                }
+                if rest.starts_with('|') {
+                    return Err(
+                        self.unexpected(LineField::Name, "a name before the other spellings")
+                    );
+                }
+            }
+            Layout::Indented => {
+                if !rest[..name_len].trim().is_empty() {
+                    return Err(self.unexpected(
+                        LineField::AltId,
+                        "an alternate henry number or the end of the fields",
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// In the indented layout, the name (and persistent id) come first
+    /// and run up to the henry number.
+    fn leading_fields(&mut self) -> Result<(), ParseErrorKind> {
+        self.skip_whitespace();
+        if self.layout != Layout::Indented {
+            return Ok(());
+        }
+
+        self.persistent_id()?;
+
+        let mut name_tokens = 0;
+        while let Some((start, token)) = self.peek_token() {
+            if is_number(token) || token.starts_with(['\\', ';']) {
+                break;
+            }
+            if name_tokens == 0 && token.starts_with('|') {
+                return Err(self.unexpected(LineField::Name, "a name before the other spellings"));
+            }
+            name_tokens += 1;
+            self.advance_past(start, token);
+            self.skip_whitespace();
+        }
+
+        if name_tokens == 0 {
+            return Err(self.unexpected(LineField::Name, "a name"));
+        }
+
+        Ok(())
+    }
+
+    /// Henry number: a sequence of numbers, non-zero and then only zeros.
+    fn henry_number(&mut self) -> Result<HenryNumber, ParseErrorKind> {
+        let mut ancestry = vec![];
+        let mut num_columns = 0;
+        let mut seen_zero = false;
+        while let Some((start, token)) = self.peek_token() {
+            if !is_number(token) {
+                break;
+            }
+
+            let value = self.number(LineField::HenryNumber, start, token)?;
+            if seen_zero && value != 0 {
+                return Err(self.error(
+                    LineField::HenryNumber,
+                    start,
+                    token,
+                    "`0` (henry numbers end in zeros)",
+                ));
+            }
+            seen_zero |= value == 0;
+            if !seen_zero {
+                ancestry.push(value);
+            }
+            num_columns += 1;
+
+            self.advance_past(start, token);
+            self.skip_whitespace();
+        }
+
+        if num_columns == 0 {
+            return Err(self.unexpected(
+                LineField::HenryNumber,
+                "a sequence of numbers separated by spaces, like `1 2 1 0 0`",
+            ));
+        }
+
+        Ok(HenryNumber { ancestry })
+    }
+
+    /// Persistent id: optional, `#` followed by a letter and then letters, digits, `-` or `_`
+    fn persistent_id(&mut self) -> Result<(), ParseErrorKind> {
+        let Some((start, token)) = self.peek_token() else {
+            return Ok(());
+        };
+
+        let Some(id) = token.strip_prefix('#') else {
+            return Ok(());
+        };
+
+        if !id.starts_with(|c: char| c.is_ascii_alphabetic())
+            || !id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(self.error(
+                LineField::PersistentId,
+                start,
+                token,
+                "`#` followed by a letter and then letters, digits, `-` or `_`",
+            ));
+        }
+
+        self.advance_past(start, token);
+        self.skip_whitespace();
+        Ok(())
+    }
+
+    /// Expects a number, which must not be run together with the next field.
+    fn expect_number(&mut self, field: LineField) -> Result<usize, ParseErrorKind> {
+        let Some((start, token)) = self.peek_token() else {
+            return Err(self.unexpected(field, "a number"));
+        };
+
+        if !is_number(token) {
+            return Err(self.unexpected(field, "a number"));
+        }
+
+        let value = self.number(field, start, token)?;
+        self.advance_past(start, token);
+        self.skip_whitespace();
+
+        Ok(value)
+    }
+
+    fn number(&self, field: LineField, start: usize, token: &str) -> Result<usize, ParseErrorKind> {
+        token
+            .parse()
+            .map_err(|_| self.error(field, start, token, "a smaller number"))
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.line[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// The next run of non-whitespace characters and its starting offset.
+    fn peek_token(&self) -> Option<(usize, &'l str)> {
+        let rest = &self.line[self.pos..];
+        let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if len == 0 {
+            None
+        } else {
+            Some((self.pos, &rest[..len]))
+        }
+    }
+
+    fn advance_past(&mut self, start: usize, token: &str) {
+        self.pos = start + token.len();
+    }
+
+    /// Error for whatever token is at the current position.
+    fn unexpected(&self, field: LineField, expected: &str) -> ParseErrorKind {
+        match self.peek_token() {
+            Some((start, token)) => self.error(field, start, token, expected),
+            None => self.error(field, self.pos, "", expected),
+        }
+    }
+
+    fn span(&self, start: usize, token: &str) -> Span {
+        Span {
+            line_num: self.line_num,
+            chars: Some((start, start + token.len())),
+        }
+    }
+
+    fn error(&self, field: LineField, start: usize, token: &str, expected: &str) -> ParseErrorKind {
+        ParseErrorKind::MalformedField {
+            field,
+            span: self.span(start, token),
+            expected: expected.to_string(),
+            found: if token.is_empty() {
+                "end of line".to_string()
+            } else {
+                format!("`{token}`")
+            },
+        }
+    }
+}
+
+/// Numbers separated by `.`, starting with a non-zero one and ending in (optional) zeros
+fn is_dotted_alt_id(s: &str) -> bool {
+    let mut seen_zero = false;
+    s.split('.').enumerate().all(|(i, part)| {
+        let zero = is_number(part) && part.chars().all(|c| c == '0');
+        let ok = is_number(part) && !(zero && i == 0) && (zero || !seen_zero);
+        seen_zero |= zero;
+        ok
+    })
+}
+
+fn is_number(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+/// A gender, which may be run together with the number of kids.
+fn is_gender(s: &str) -> bool {
+    s.strip_prefix(['M', 'F', '?'])
+        .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit()))
+}
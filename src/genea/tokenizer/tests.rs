@@ -0,0 +1,173 @@
+//! The field that the tokenizer blames for each kind of malformed person line.
+
+use super::{diagnose, henry_number, looks_like_person_line, LineField};
+use crate::genea::{error::ParseErrorKind, parser::Layout};
+
+/// The field blamed for `line`, the columns of the text blamed and what was found there.
+fn malformed(line: &str) -> (LineField, (usize, usize), String) {
+    malformed_in(line, Layout::HenryFirst)
+}
+
+fn malformed_in(line: &str, layout: Layout) -> (LineField, (usize, usize), String) {
+    match diagnose(line, 1, layout) {
+        Some(ParseErrorKind::MalformedField {
+            field, span, found, ..
+        }) => (field, span.chars.unwrap(), found),
+        kind => panic!("expected a malformed field in {line:?}, got {kind:?}"),
+    }
+}
+
+#[test]
+fn valid_line_has_nothing_to_diagnose() {
+    assert!(diagnose(
+        " 1 1 M 2 1 0 11 Spock \\ comment; private",
+        1,
+        Layout::HenryFirst
+    )
+    .is_none());
+}
+
+#[test]
+fn missing_henry_number() {
+    assert_eq!(
+        malformed(" Spock"),
+        (LineField::HenryNumber, (1, 6), "`Spock`".to_string())
+    );
+}
+
+#[test]
+fn henry_number_with_a_zero_in_the_middle() {
+    assert_eq!(
+        malformed(" 1 0 2 M 0 0 0 Spock"),
+        (LineField::HenryNumber, (5, 6), "`2`".to_string())
+    );
+}
+
+#[test]
+fn henry_number_too_large() {
+    assert_eq!(
+        malformed(" 99999999999999999999999 M 0 0 0 Spock").0,
+        LineField::HenryNumber
+    );
+}
+
+#[test]
+fn unknown_gender() {
+    assert_eq!(
+        malformed(" 1 1 X 0 0 0 Spock"),
+        (LineField::Gender, (5, 6), "`X`".to_string())
+    );
+}
+
+#[test]
+fn missing_number_of_kids() {
+    assert_eq!(
+        malformed(" 1 1 M x 0 0 Spock"),
+        (LineField::NumKids, (7, 8), "`x`".to_string())
+    );
+}
+
+#[test]
+fn missing_number_of_spouses() {
+    assert_eq!(
+        malformed(" 1 1 M 0 Spock"),
+        (LineField::NumSpouses, (9, 14), "`Spock`".to_string())
+    );
+}
+
+#[test]
+fn missing_spousal_index() {
+    assert_eq!(
+        malformed(" 1 1 M 0 0 Spock"),
+        (LineField::SpousalIndex, (11, 16), "`Spock`".to_string())
+    );
+}
+
+#[test]
+fn alternate_henry_number_with_a_zero_in_the_middle() {
+    let Some(ParseErrorKind::AmbiguousAltId {
+        alt_id_span,
+        suggested_hn,
+        ..
+    }) = diagnose(" 1 1 M 0 0 0 1021 Spock", 1, Layout::HenryFirst)
+    else {
+        panic!("expected an ambiguous alternate henry number");
+    };
+    assert_eq!(alt_id_span.chars, Some((13, 17)));
+    assert_eq!(suggested_hn, "10 2 1".parse().unwrap());
+}
+
+#[test]
+fn dotted_alternate_henry_number_with_a_zero() {
+    assert_eq!(
+        malformed(" 1 1 M 0 0 0 1.0.2 Spock"),
+        (LineField::AltId, (13, 18), "`1.0.2`".to_string())
+    );
+}
+
+#[test]
+fn persistent_id_must_start_with_a_letter() {
+    assert!(diagnose(" 1 1 M 0 0 0 #spock-1 Spock", 1, Layout::HenryFirst).is_none());
+    assert_eq!(
+        malformed(" 1 1 M 0 0 0 #1spock Spock"),
+        (LineField::PersistentId, (13, 20), "`#1spock`".to_string())
+    );
+    assert_eq!(
+        malformed_in("  #sp.ck Spock 1 1 M 0 0 0", Layout::Indented),
+        (LineField::PersistentId, (2, 8), "`#sp.ck`".to_string())
+    );
+}
+
+#[test]
+fn missing_name() {
+    assert_eq!(
+        malformed(" 1 1 M 0 0 0 \\ comment"),
+        (LineField::Name, (13, 14), "`\\`".to_string())
+    );
+    assert_eq!(
+        malformed(" 1 1 M 0 0 0"),
+        (LineField::Name, (12, 12), "end of line".to_string())
+    );
+}
+
+#[test]
+fn missing_name_in_the_indented_layout() {
+    assert_eq!(
+        malformed_in("   1 1 M 0 0 0", Layout::Indented),
+        (LineField::Name, (3, 4), "`1`".to_string())
+    );
+}
+
+#[test]
+fn name_after_the_fields_in_the_indented_layout() {
+    assert_eq!(
+        malformed_in("  Spock 1 1 M 0 0 0 Spock", Layout::Indented),
+        (LineField::AltId, (20, 25), "`Spock`".to_string())
+    );
+}
+
+#[test]
+fn henry_number_of_a_malformed_line() {
+    let layout = Layout::HenryFirst;
+    assert_eq!(
+        henry_number(" 1 2 0 F two 0 0 T'Pau", layout),
+        Some("1 2".parse().unwrap())
+    );
+    assert_eq!(henry_number(" 1 2 generations back", layout), None);
+    assert_eq!(
+        henry_number("  T'Pau 1 2 0 F two 0 0", Layout::Indented),
+        Some("1 2".parse().unwrap())
+    );
+}
+
+#[test]
+fn person_lines_start_with_numbers_and_a_gender() {
+    assert!(looks_like_person_line(" 1 1 M 0 0 0 Spock"));
+    assert!(looks_like_person_line(" 1 2 F2 0 0 T'Pau"));
+    assert!(looks_like_person_line(" 1 M"));
+    assert!(!looks_like_person_line(
+        "1 2 generations back, from the paper records"
+    ));
+    assert!(!looks_like_person_line("1 Male line only"));
+    assert!(!looks_like_person_line("Printed 3 March"));
+}
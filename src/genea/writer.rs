@@ -0,0 +1,236 @@
+//! Writing a family tree back out in the `genea.doc` format.
+
+use std::{collections::BTreeMap, path::Path};
+
+use itertools::Itertools;
+
+use super::{parser, Gender, Genea, HenryNumber, ParseOptions, Partnership, Person};
+
+#[cfg(test)]
+mod tests;
+
+/// The number of henry number columns used in the printouts; deeper trees get more.
+const MIN_HENRY_COLUMNS: usize = 10;
+
+/// The width that compact alternate henry numbers are padded to with zeros, as in the printouts.
+const ALT_ID_WIDTH: usize = 7;
+
+impl Genea {
+    /// Writes the tree in the `genea.doc` format: the preamble, followed by the person lines in
+    /// depth-first order with aligned columns (see [`parser::format_text`]). Everybody is listed
+    /// after their parent, and spouses (followed by their children) right after their partner.
+    /// People who appear on several lines are linked by alternate henry numbers, and their
+    /// aliases, persistent id and comments are written on the first of those lines. The counts
+    /// of kids and spouses are the declared ones, even where they don't match the tree.
+    /// Blank lines at the end of the file are kept, as is a `\` with no comments after it,
+    /// so a file that is already laid out this way is written back exactly as it was.
+    ///
+    /// The lines are always written with the henry numbers first, as `genea.doc` is kept, even
+    /// if the tree was read from an indented printout: converting such a printout is one way
+    /// to bring it into `genea.doc`.
+    ///
+    /// Fails if the result is not a valid `genea.doc` file, which can happen if the tree
+    /// was edited into an inconsistent state.
+    pub fn to_genea_doc(&self) -> anyhow::Result<String> {
+        let mut writer = Writer {
+            genea: self,
+            lines: vec![],
+            columns: MIN_HENRY_COLUMNS.max(
+                self.people()
+                    .filter_map(|p| self[p].henry_number())
+                    .map(|hn| hn.ancestry.len())
+                    .max()
+                    .unwrap_or(0),
+            ),
+        };
+
+        for person in self
+            .root_people()
+            .sorted_by_key(|&p| self[p].henry_number.clone())
+        {
+            writer.write_descendant(person);
+        }
+
+        let mut text = self.preamble.clone();
+        text.extend(writer.lines_text());
+        text.push_str(&self.epilogue);
+        parser::format_text("<generated>", &text)
+    }
+}
+
+/// Regenerates the `genea.doc` file at `path` from the tree it describes (see
+/// [`Genea::to_genea_doc`]), writing the result to `output_path`, or to stdout if it is `None`.
+///
+/// As a check on the writer, the result is parsed again and must describe the same tree,
+/// which means that writing it once more gives back the same text.
+pub fn write_genea_doc(path: &Path, output_path: Option<&Path>) -> anyhow::Result<()> {
+    let genea = Genea::from_genea_doc(path)?;
+    let text = genea.to_genea_doc()?;
+
+    let reparsed = parser::parse_text("<generated>", &text, ParseOptions::default())?;
+    if reparsed.to_genea_doc()? != text {
+        anyhow::bail!(
+            "the tree in `{}` changed when written out and read back in",
+            path.display()
+        );
+    }
+
+    match output_path {
+        Some(output_path) => std::fs::write(output_path, text)?,
+        None => print!("{text}"),
+    }
+
+    Ok(())
+}
+
+/// A person line, before it is laid out in columns.
+struct Line {
+    person: Person,
+
+    /// The henry number of the family member whose line this is (for a spouse line, their partner)
+    henry_number: HenryNumber,
+
+    gender: Gender,
+    num_kids: usize,
+    num_spouses: usize,
+    spousal_index: usize,
+}
+
+struct Writer<'g> {
+    genea: &'g Genea,
+    lines: Vec<Line>,
+    columns: usize,
+}
+
+impl Writer<'_> {
+    /// Writes the line for `person`, who must have a henry number, followed by their
+    /// children and the partnerships listed under them.
+    fn write_descendant(&mut self, person: Person) {
+        let genea = self.genea;
+        let person_data = &genea[person];
+
+        let hosted: Vec<Partnership> = person_data
+            .parent_in
+            .iter()
+            .copied()
+            .filter(|&p| genea[p].primary_parent == person)
+            .collect();
+        let (with_partner, without_partner): (Vec<Partnership>, Vec<Partnership>) = hosted
+            .into_iter()
+            .partition(|&p| genea[p].other_parent(person).is_some());
+
+        self.lines.push(Line {
+            person,
+            henry_number: person_data.henry_number.clone().unwrap(),
+            gender: person_data.gender,
+            num_kids: person_data.num_kids,
+            num_spouses: person_data.num_spouses,
+            spousal_index: 0,
+        });
+
+        // Children listed without a spouse come straight after the person...
+        for partnership in without_partner {
+            self.write_children(partnership);
+        }
+
+        // ...and then each spouse, followed by their children.
+        let with_partner = with_partner
+            .into_iter()
+            .enumerate()
+            .sorted_by_key(|&(i, p)| genea[p].spousal_index.map(|s| s.0).unwrap_or(i + 1));
+        for (i, partnership) in with_partner {
+            let partner = genea[partnership].other_parent(person).unwrap();
+            self.lines.push(Line {
+                person: partner,
+                henry_number: person_data.henry_number.clone().unwrap(),
+                gender: genea[partner].gender,
+                num_kids: genea[partnership].num_kids,
+                num_spouses: 0,
+                spousal_index: genea[partnership]
+                    .spousal_index
+                    .map(|s| s.0)
+                    .unwrap_or(i + 1),
+            });
+            self.write_children(partnership);
+        }
+    }
+
+    fn write_children(&mut self, partnership: Partnership) {
+        let genea = self.genea;
+        for child in genea[partnership]
+            .children
+            .iter()
+            .copied()
+            .sorted_by_key(|&c| genea[c].henry_number.clone())
+        {
+            self.write_descendant(child);
+        }
+    }
+
+    /// The text of the lines, with single spaces between the columns.
+    fn lines_text(&self) -> impl Iterator<Item = String> + '_ {
+        let genea = self.genea;
+
+        // Every line of somebody who appears more than once names the henry number
+        // of another one of their lines, so that they are recognized as the same person.
+        let mut lines_of: BTreeMap<Person, Vec<usize>> = BTreeMap::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            lines_of.entry(line.person).or_default().push(index);
+        }
+
+        self.lines.iter().enumerate().map(move |(index, line)| {
+            let person_data = &genea[line.person];
+            let person_lines = &lines_of[&line.person];
+            let first = person_lines[0] == index;
+
+            let mut text = String::new();
+            for column in 0..self.columns {
+                let n = line.henry_number.ancestry.get(column).copied().unwrap_or(0);
+                text.push_str(&format!(" {n}"));
+            }
+
+            let gender = match line.gender {
+                Gender::Male => "M",
+                Gender::Female => "F",
+                Gender::Unknown => "?",
+            };
+            text.push_str(&format!(
+                " {gender} {} {} {}",
+                line.num_kids, line.num_spouses, line.spousal_index
+            ));
+
+            let other_line = person_lines.iter().find(|&&i| i != index);
+            if let Some(&other_line) = other_line {
+                let alt_id = self.lines[other_line].henry_number.to_alt_string();
+                if alt_id.contains('.') {
+                    text.push_str(&format!(" {alt_id}"));
+                } else {
+                    text.push_str(&format!(" {alt_id:0<ALT_ID_WIDTH$}"));
+                }
+            }
+
+            text.push(' ');
+            if first {
+                if let Some(id) = &person_data.persistent_id {
+                    text.push_str(&format!("#{id} "));
+                }
+                text.push_str(
+                    &std::iter::once(&person_data.name)
+                        .chain(&person_data.aliases)
+                        .join("|"),
+                );
+                if !person_data.comments.is_empty() || person_data.blank_comments {
+                    text.push_str(&format!("\\{}", person_data.comments));
+                }
+                if !person_data.private_comments.is_empty() {
+                    text.push_str(&format!(";{}", person_data.private_comments));
+                }
+            } else {
+                text.push_str(&person_data.name);
+            }
+
+            text.push('\n');
+            text
+        })
+    }
+}
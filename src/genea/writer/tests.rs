@@ -0,0 +1,173 @@
+//! Writing a tree out and reading it back in should give the same tree.
+
+use itertools::Itertools;
+
+use super::super::{
+    parser::parse_text,
+    testing::{mutate, random_genea_doc, Rng, GENEA_DOC},
+    Genea, ParseOptions, Partnership,
+};
+
+/// Everything the file says about the tree, one line per person and per partnership,
+/// referring to people by id so that it doesn't depend on the order they were read in.
+fn describe(genea: &Genea) -> Vec<String> {
+    let partnership = |p: Partnership| {
+        let partnership_data = &genea[p];
+        format!(
+            "{} with {} (spouse {:?})",
+            genea.id(partnership_data.primary_parent),
+            partnership_data
+                .parents
+                .iter()
+                .filter(|&&parent| parent != partnership_data.primary_parent)
+                .map(|&parent| genea.id(parent))
+                .join(" and "),
+            partnership_data.spousal_index,
+        )
+    };
+
+    let people = genea.people().map(|person| {
+        let person_data = &genea[person];
+        format!(
+            "{}: {:?} {:?} {:?} {:?} {} {} #{:?} \\{:?} ;{:?}, child of {:?}",
+            genea.id(person),
+            person_data.name,
+            person_data.aliases,
+            person_data.gender,
+            person_data.henry_number,
+            person_data.num_kids,
+            person_data.num_spouses,
+            person_data.persistent_id,
+            person_data.comments,
+            person_data.private_comments,
+            person_data.child_in.map(partnership),
+        )
+    });
+
+    let partnerships = genea.partnerships().map(|p| {
+        format!(
+            "{}: {} kids, children {}",
+            partnership(p),
+            genea[p].num_kids,
+            genea[p].children.iter().map(|&c| genea.id(c)).join(", ")
+        )
+    });
+
+    people.chain(partnerships).sorted().collect()
+}
+
+/// Checks that `text` describes the same tree after it has been written out again,
+/// and that writing that tree gives back the same text.
+fn assert_round_trip(text: &str) {
+    let genea = parse_text("genea.doc", text, ParseOptions::default())
+        .unwrap_or_else(|e| panic!("{e}\nin:\n{text}"));
+    let written = genea
+        .to_genea_doc()
+        .unwrap_or_else(|e| panic!("{e}\nwriting:\n{text}"));
+    let reparsed = parse_text("<generated>", &written, ParseOptions::default())
+        .unwrap_or_else(|e| panic!("{e}\nwritten from:\n{text}"));
+
+    let (before, after) = (describe(&genea), describe(&reparsed));
+    let changes: Vec<String> = before
+        .iter()
+        .filter(|line| !after.contains(line))
+        .map(|line| format!("- {line}"))
+        .chain(
+            after
+                .iter()
+                .filter(|line| !before.contains(line))
+                .map(|line| format!("+ {line}")),
+        )
+        .collect();
+    assert!(
+        changes.is_empty(),
+        "the tree changed when written out:\n{}\nfrom:\n{text}\nto:\n{written}",
+        changes.join("\n")
+    );
+    assert_eq!(reparsed.to_genea_doc().unwrap(), written);
+}
+
+#[test]
+fn genea_doc_round_trips() {
+    assert_round_trip(GENEA_DOC);
+}
+
+#[test]
+fn random_trees_round_trip() {
+    let mut rng = Rng(0x853c_49e6_748f_ea9b);
+    for _ in 0..200 {
+        assert_round_trip(&random_genea_doc(&mut rng));
+    }
+}
+
+/// Lines that are moved around or changed may still make a valid tree, perhaps
+/// one that `genea.doc` would never be written as.
+#[test]
+fn mutated_trees_round_trip() {
+    let mut rng = Rng(0xda94_2042_e4dd_58b5);
+    for i in 0..600 {
+        let text = match i % 2 {
+            0 => GENEA_DOC.to_string(),
+            _ => random_genea_doc(&mut rng),
+        };
+        let mut lines: Vec<String> = text.lines().map(String::from).collect();
+        mutate(&mut rng, &mut lines);
+        let text = lines.join("\n") + "\n";
+        if parse_text("genea.doc", &text, ParseOptions::default()).is_ok() {
+            assert_round_trip(&text);
+        }
+    }
+}
+
+#[test]
+fn indented_printouts_are_written_with_the_henry_numbers_first() {
+    let text = "Skon 1 0 M 1 1 0\nT'Sai 1 0 F 1 0 1\n  Sarek 1 1 M 0 0 0 \\ ambassador\n";
+    assert_round_trip(text);
+
+    let genea = parse_text("genea.doc", text, ParseOptions::default()).unwrap();
+    let written = genea.to_genea_doc().unwrap();
+    assert_eq!(
+        written.lines().collect::<Vec<_>>(),
+        [
+            " 1 0 0 0 0 0 0 0 0 0 M 1 1 0 Skon",
+            " 1 0 0 0 0 0 0 0 0 0 F 1 0 1 T'Sai",
+            " 1 1 0 0 0 0 0 0 0 0 M 0 0 0 Sarek\\ ambassador",
+        ]
+    );
+}
+
+#[test]
+fn declared_counts_are_kept_even_when_they_dont_match() {
+    let text = " 1 0 M 2 1 0 Sarek\n 1 0 F 3 0 1 Amanda\n 1 1 M 0 0 0 Spock\n";
+    assert_round_trip(text);
+
+    let genea = parse_text("genea.doc", text, ParseOptions::default()).unwrap();
+    let written = genea.to_genea_doc().unwrap();
+    assert!(written.contains(" M 2 1 0 Sarek\n"), "{written}");
+    assert!(written.contains(" F 3 0 1 Amanda\n"), "{written}");
+}
+
+#[test]
+fn people_on_several_lines_are_linked_by_alt_ids() {
+    let genea = parse_text(
+        "genea.doc",
+        " 1 0 M 0 1 0 Stonn\n 1 0 F 0 0 1 21 T'Pau\n 2 0 M 1 0 0 Sarek\n 2 1 F 0 0 0 T'Pau\\ matriarch\n",
+        ParseOptions::default(),
+    )
+    .unwrap();
+    let written = genea.to_genea_doc().unwrap();
+    let t_pau: Vec<&str> = written.lines().filter(|l| l.contains("T'Pau")).collect();
+    assert_eq!(
+        t_pau,
+        [
+            " 1 0 0 0 0 0 0 0 0 0 F 0 0 1 2100000 T'Pau\\ matriarch",
+            " 2 1 0 0 0 0 0 0 0 0 F 0 0 0 1000000 T'Pau",
+        ]
+    );
+}
+
+#[test]
+fn genea_doc_is_written_back_exactly() {
+    let genea = parse_text("genea.doc", GENEA_DOC, ParseOptions::default()).unwrap();
+    assert_eq!(genea.to_genea_doc().unwrap(), GENEA_DOC);
+}
@@ -1,6 +1,12 @@
 use std::{collections::BTreeSet, path::Path};
 
-use crate::genea::{Genea, Person};
+use crate::{
+    genea::{Genea, Person},
+    redirects::Redirects,
+};
+
+#[cfg(test)]
+mod tests;
 
 pub fn generate(genea: &Genea, output_path: &Path) -> anyhow::Result<()> {
     std::fs::create_dir_all(output_path)?;
@@ -18,6 +24,35 @@ pub fn generate(genea: &Genea, output_path: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Writes a page for each old id in `redirects` that sends the browser on to the
+/// person's current page.
+pub fn generate_redirects(redirects: &Redirects, output_path: &Path) -> anyhow::Result<()> {
+    use std::fmt::Write;
+
+    std::fs::create_dir_all(output_path)?;
+
+    for (old_id, new_id) in redirects {
+        let mut contents = String::new();
+        writeln!(contents, "<head>")?;
+        writeln!(
+            contents,
+            r#"<meta http-equiv="refresh" content="0; url={new_id}.html">"#
+        )?;
+        writeln!(contents, "</head>")?;
+        writeln!(contents, "<body>")?;
+        writeln!(
+            contents,
+            r#"<p>Moved to <a href="{new_id}.html">{new_id}</a></p>"#
+        )?;
+        writeln!(contents, "</body>")?;
+
+        let path = output_path.join(old_id).with_extension("html");
+        std::fs::write(path, contents)?;
+    }
+
+    Ok(())
+}
+
 struct HtmlGen<'a> {
     page_person: Option<Person>,
     genea: &'a Genea,
@@ -57,7 +92,7 @@ impl<'a> HtmlGen<'a> {
 
         let path = self
             .output_path
-            .join(self.id(page_person))
+            .join(self.genea.id(page_person))
             .with_extension("html");
 
         let mut contents = String::new();
@@ -75,10 +110,9 @@ impl<'a> HtmlGen<'a> {
         writeln!(wr, "<p>")?;
 
         writeln!(wr, "<ul>")?;
-        if Some(person) == self.page_person && person_data.child_in.is_some() {
-            let child_in = person_data.child_in.unwrap();
+        if let (true, Some(child_in)) = (Some(person) == self.page_person, person_data.child_in) {
             let parents = &self.genea[child_in].parents;
-            self.outline_parents(wr, person, &parents)?;
+            self.outline_parents(wr, person, parents)?;
         } else {
             self.outline_line_items(wr, person)?;
         }
@@ -127,7 +161,7 @@ impl<'a> HtmlGen<'a> {
                     partner_link = self.person_link(partner)
                 )?;
             }
-            writeln!(wr, "")?;
+            writeln!(wr)?;
 
             if !partnership_data.children.is_empty() {
                 writeln!(wr, "<ul>")?;
@@ -146,28 +180,9 @@ impl<'a> HtmlGen<'a> {
         } else {
             format!(
                 r#"<a href="{id}.html">{name}</a>"#,
-                id = self.id(person),
+                id = self.genea.id(person),
                 name = self.genea[person].name,
             )
         }
     }
-
-    fn id(&self, person: Person) -> String {
-        let person_data = &self.genea[person];
-        match &person_data.henry_number {
-            Some(hn) => hn.to_string(),
-            None => {
-                assert_eq!(person_data.parent_in.len(), 1);
-                let parent_in = *person_data.parent_in.first().unwrap();
-                let partner = self.genea[parent_in].other_parent(person).unwrap();
-                let hn = self.genea[partner].henry_number().unwrap();
-                let spousal_index = self.genea[partner]
-                    .parent_in
-                    .iter()
-                    .position(|p| *p == parent_in)
-                    .unwrap();
-                format!("{hn}--{spousal_index}")
-            }
-        }
-    }
 }
@@ -0,0 +1,34 @@
+//! The pages generated for people who are hard to identify.
+
+use super::{generate_redirects, HtmlGen};
+use crate::{
+    genea::{Genea, ParseOptions},
+    redirects::Redirects,
+};
+
+#[test]
+fn spouse_of_two_family_members_shows_both_partnerships() {
+    // T'Rea married Sarek and, later, his brother Sybok.
+    let text = " 1 0 M 2 0 0 Skon\n 1 1 M 0 1 0 Sarek\n 1 1 F 0 2 1 T'Rea\n 1 2 M 0 1 0 Sybok\n 1 2 F 0 2 1 11 T'Rea\n";
+    let genea = Genea::from_genea_text("genea.doc", text, ParseOptions::default()).unwrap();
+    let t_rea = genea.people().find(|&p| genea[p].name == "T'Rea").unwrap();
+
+    let mut gen = HtmlGen::new(&genea, "html".as_ref());
+    gen.page_person = Some(t_rea);
+    let mut page = String::new();
+    gen.person_string(&mut page, t_rea).unwrap();
+
+    assert!(page.contains(r#"<li> <b>T'Rea</b> + <a href="1-1.html">Sarek</a>"#));
+    assert!(page.contains(r#"<li> <b>T'Rea</b> + <a href="1-2.html">Sybok</a>"#));
+}
+
+#[test]
+fn redirect_stub_sends_the_browser_to_the_new_page() {
+    let dir = std::env::temp_dir().join(format!("family-tree-html-{}", std::process::id()));
+    let redirects = Redirects::from([("1-1".to_string(), "2-1".to_string())]);
+    generate_redirects(&redirects, &dir).unwrap();
+
+    let stub = std::fs::read_to_string(dir.join("1-1.html")).unwrap();
+    assert!(stub.contains(r#"<meta http-equiv="refresh" content="0; url=2-1.html">"#));
+    let _ = std::fs::remove_dir_all(&dir);
+}
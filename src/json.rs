@@ -7,6 +7,9 @@ use crate::{
     json_api::{Datum, Ref, Response, ToManyRelationship, ToOneRelationship},
 };
 
+#[cfg(test)]
+mod tests;
+
 pub fn generate(genea: &Genea, output_path: impl AsRef<Path>) -> anyhow::Result<()> {
     let output_path = output_path.as_ref();
 
@@ -46,6 +49,8 @@ struct PersonAttributes {
     name: String,
     comments: String,
     gender: String,
+    #[serde(rename = "henryNumber")]
+    henry_number: Option<String>,
     #[serde(rename = "isSpouse")]
     is_spouse: bool,
 }
@@ -73,14 +78,6 @@ impl<'a> JsonGen<'a> {
         Response::new(self.root_datum()).include(self.all_datums())
     }
 
-    fn person_response(&self, person: Person) -> Response {
-        Response::new(self.person_datum(person))
-    }
-
-    fn partnership_response(&self, partnership: Partnership) -> Response {
-        Response::new(self.partnership_datum(partnership))
-    }
-
     fn root_datum(&self) -> Datum {
         Datum::new(
             "root",
@@ -109,11 +106,12 @@ impl<'a> JsonGen<'a> {
         let person_data = &self.genea[person];
         Datum::new(
             "person",
-            self.id(person),
+            self.genea.id(person),
             PersonAttributes {
                 name: person_data.name.to_string(),
                 comments: person_data.comments.clone(),
                 gender: person_data.gender.to_string(),
+                henry_number: person_data.henry_number().map(|hn| hn.to_string()),
                 is_spouse: person_data.henry_number().is_none(),
             },
             PersonRelationships {
@@ -128,7 +126,7 @@ impl<'a> JsonGen<'a> {
     }
 
     fn person_ref(&self, person: Person) -> Ref {
-        Ref::new("person", self.id(person))
+        Ref::new("person", self.genea.id(person))
     }
 
     fn partnership_datum(&self, partnership: Partnership) -> Datum {
@@ -155,23 +153,4 @@ impl<'a> JsonGen<'a> {
     fn partnership_ref(&self, partnership: Partnership) -> Ref {
         Ref::new("partnership", partnership.as_usize())
     }
-
-    fn id(&self, person: Person) -> String {
-        let person_data = &self.genea[person];
-        match &person_data.henry_number {
-            Some(hn) => hn.to_string(),
-            None => {
-                assert_eq!(person_data.parent_in.len(), 1);
-                let parent_in = *person_data.parent_in.first().unwrap();
-                let partner = self.genea[parent_in].other_parent(person).unwrap();
-                let hn = self.genea[partner].henry_number().unwrap();
-                let spousal_index = self.genea[partner]
-                    .parent_in
-                    .iter()
-                    .position(|p| *p == parent_in)
-                    .unwrap();
-                format!("{hn}--{spousal_index}")
-            }
-        }
-    }
 }
@@ -0,0 +1,52 @@
+//! The JSON API output for people who are hard to identify.
+
+use serde_json::json;
+
+use super::JsonGen;
+use crate::genea::{Genea, ParseOptions};
+
+#[test]
+fn spouse_of_two_family_members_links_both_partnerships() {
+    // T'Rea married Sarek and, later, his brother Sybok.
+    let text = " 1 0 M 2 0 0 Skon\n 1 1 M 0 1 0 Sarek\n 1 1 F 0 2 1 T'Rea\n 1 2 M 0 1 0 Sybok\n 1 2 F 0 2 1 11 T'Rea\n";
+    let genea = Genea::from_genea_text("genea.doc", text, ParseOptions::default()).unwrap();
+    let t_rea = genea.people().find(|&p| genea[p].name == "T'Rea").unwrap();
+
+    let datum = serde_json::to_value(JsonGen::new(&genea).person_datum(t_rea)).unwrap();
+    assert_eq!(datum["id"], json!("1-1--1"));
+    assert_eq!(datum["attributes"]["isSpouse"], json!(true));
+
+    let partnerships = datum["relationships"]["parentIn"]["data"]
+        .as_array()
+        .unwrap();
+    assert_eq!(partnerships.len(), 2);
+    let partners: Vec<String> = genea[t_rea]
+        .parent_in
+        .iter()
+        .map(|&p| genea.id(genea[p].other_parent(t_rea).unwrap()))
+        .collect();
+    assert_eq!(partners, ["1-1", "1-2"]);
+}
+
+#[test]
+fn persistent_id_is_the_id_and_henry_number_an_attribute() {
+    let text = " 1 0 M 1 0 0 #skon Skon\n 1 1 M 0 0 0 Sarek\n";
+    let genea = Genea::from_genea_text("genea.doc", text, ParseOptions::default()).unwrap();
+    let gen = JsonGen::new(&genea);
+    let datums: Vec<_> = genea
+        .people()
+        .map(|p| serde_json::to_value(gen.person_datum(p)).unwrap())
+        .collect();
+
+    assert_eq!(datums[0]["id"], json!("skon"));
+    assert_eq!(datums[0]["attributes"]["henryNumber"], json!("1"));
+    assert_eq!(datums[1]["id"], json!("1-1"));
+
+    // Links to the person use the persistent id too.
+    let partnership = genea.partnerships().next().unwrap();
+    let partnership = serde_json::to_value(gen.partnership_datum(partnership)).unwrap();
+    assert_eq!(
+        partnership["relationships"]["parents"]["data"][0]["id"],
+        json!("skon")
+    );
+}
@@ -1,20 +1,112 @@
-use std::{path::PathBuf, process::Command};
+use std::{path::PathBuf, process::Command, str::FromStr};
 
-use genea::Genea;
+use genea::{
+    assign_person_ids, fix_genea_doc, format_genea_doc, insert_child_into_genea_doc,
+    move_child_in_genea_doc, write_genea_doc, Gender, Genea, ParseErrors, ParseOptions,
+};
 use outline::Outline;
 use structopt::StructOpt;
 
-mod genea;
+mod diff;
+mod editor;
+pub mod genea;
 mod html;
 mod json;
 mod json_api;
 mod outline;
+mod redirects;
 
 #[derive(structopt::StructOpt)]
 enum Args {
     Check {
         #[structopt(long)]
         dump: bool,
+
+        /// How to report problems: `human` or `json`
+        #[structopt(long, default_value = "human")]
+        format: OutputFormat,
+
+        /// Apply the suggested fixes to the file in place
+        #[structopt(long)]
+        fix: bool,
+
+        /// With `--fix`, print the changes as a diff instead of writing them
+        #[structopt(long, requires = "fix")]
+        dry_run: bool,
+
+        /// Require the file to be in depth-first order, with everybody listed
+        /// after their parent and spouses listed right after their partner
+        #[structopt(long)]
+        strict_order: bool,
+
+        genea_path: PathBuf,
+    },
+
+    /// Give a persistent id to everybody who does not have one yet
+    AssignIds {
+        /// Print the changes as a diff instead of writing them
+        #[structopt(long)]
+        dry_run: bool,
+
+        genea_path: PathBuf,
+    },
+
+    /// Lay out the person lines in aligned columns
+    Fmt {
+        /// Print the changes as a diff instead of writing them, failing if there are any
+        #[structopt(long)]
+        check: bool,
+
+        genea_path: PathBuf,
+    },
+
+    /// Add a child, renumbering their younger siblings and everybody descended from them
+    Insert {
+        /// The henry number of the parent, e.g. `1-1-1`
+        #[structopt(long)]
+        under: String,
+
+        /// The spousal index of the other parent, if the parent has several spouses
+        #[structopt(long)]
+        spouse: Option<usize>,
+
+        /// Where the child goes among their siblings, counting from 1 (by default, last)
+        #[structopt(long)]
+        position: Option<usize>,
+
+        /// Print the changes as a diff instead of writing them
+        #[structopt(long)]
+        dry_run: bool,
+
+        name: String,
+
+        /// `M`, `F` or `?`
+        gender: Gender,
+
+        #[structopt(default_value = "genea.doc")]
+        genea_path: PathBuf,
+    },
+
+    /// Edit the tree interactively, with commands read from stdin
+    Edit {
+        genea_path: PathBuf,
+    },
+
+    /// Move a child to another position among their siblings, renumbering everybody
+    /// descended from the siblings in between
+    Move {
+        /// The henry number of the child, e.g. `1-1-1-3`
+        henry_number: String,
+
+        /// The new position among their siblings, counting from 1
+        #[structopt(long)]
+        position: usize,
+
+        /// Print the changes as a diff instead of writing them
+        #[structopt(long)]
+        dry_run: bool,
+
+        #[structopt(default_value = "genea.doc")]
         genea_path: PathBuf,
     },
 
@@ -22,6 +114,26 @@ enum Args {
         genea_path: PathBuf,
     },
 
+    /// Regenerate `genea.doc` from the tree it describes, in depth-first order and
+    /// laid out in columns (always with the henry numbers first, even if read from an
+    /// indented printout)
+    Write {
+        genea_path: PathBuf,
+
+        /// Where to write the result (by default, stdout)
+        output_path: Option<PathBuf>,
+    },
+
+    /// Describe how the people in two versions of `genea.doc` differ
+    Diff {
+        /// How to report the changes: `human` or `json`
+        #[structopt(long, default_value = "human")]
+        format: OutputFormat,
+
+        old_genea_path: PathBuf,
+        genea_path: PathBuf,
+    },
+
     Json {
         genea_path: PathBuf,
         output_path: PathBuf,
@@ -32,6 +144,21 @@ enum Args {
         output_path: PathBuf,
     },
 
+    /// Compare with a previous version of `genea.doc` and record the new ids of people
+    /// whose ids changed, so that old links can be redirected
+    Redirects {
+        previous_genea_path: PathBuf,
+        genea_path: PathBuf,
+
+        /// The table of redirects, as JSON; entries from an existing table are kept.
+        /// The site looks for it at `public/redirects.json`.
+        output_path: PathBuf,
+
+        /// Also write redirect pages into this `html` output directory
+        #[structopt(long)]
+        html: Option<PathBuf>,
+    },
+
     Serve {
         genea_path: PathBuf,
     },
@@ -41,6 +168,24 @@ enum Args {
     },
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Human,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "human" => Ok(OutputFormat::Human),
+            "json" => Ok(OutputFormat::Json),
+            _ => anyhow::bail!("unrecognized format `{s}`, expected `human` or `json`"),
+        }
+    }
+}
+
 pub fn main() -> anyhow::Result<()> {
     let args: Args = Args::from_args();
 
@@ -48,18 +193,109 @@ pub fn main() -> anyhow::Result<()> {
         Args::Check {
             genea_path: path,
             dump,
+            format,
+            fix,
+            dry_run,
+            strict_order,
         } => {
-            let genea = Genea::from_genea_doc(path)?;
+            let options = ParseOptions {
+                strict_order: *strict_order,
+            };
+
+            if *fix {
+                fix_genea_doc(path, *dry_run, options)?;
+            }
+
+            let genea = match (Genea::from_genea_doc_with_options(path, options), format) {
+                (Ok(genea), _) => genea,
+                (Err(e), OutputFormat::Json) => {
+                    let Some(errors) = e.downcast_ref::<ParseErrors>() else {
+                        return Err(e);
+                    };
+                    println!("{}", serde_json::to_string(&errors.diagnostics())?);
+                    std::process::exit(1);
+                }
+                (Err(e), OutputFormat::Human) => return Err(e),
+            };
+
+            match format {
+                OutputFormat::Human => {
+                    for warning in genea.warnings() {
+                        eprintln!("{warning}");
+                    }
+                }
+                OutputFormat::Json => {
+                    let diagnostics: Vec<_> =
+                        genea.warnings().iter().map(|w| w.diagnostic()).collect();
+                    println!("{}", serde_json::to_string(&diagnostics)?);
+                }
+            }
 
             if *dump {
                 eprintln!("{genea:#?}")
             }
         }
+        Args::AssignIds {
+            genea_path,
+            dry_run,
+        } => {
+            assign_person_ids(genea_path, *dry_run)?;
+        }
+        Args::Fmt { genea_path, check } => {
+            format_genea_doc(genea_path, *check)?;
+        }
+        Args::Insert {
+            under,
+            spouse,
+            position,
+            dry_run,
+            name,
+            gender,
+            genea_path,
+        } => {
+            insert_child_into_genea_doc(
+                genea_path, under, *spouse, *position, name, *gender, *dry_run,
+            )?;
+        }
+        Args::Edit { genea_path } => {
+            editor::run(genea_path)?;
+        }
+        Args::Move {
+            henry_number,
+            position,
+            dry_run,
+            genea_path,
+        } => {
+            move_child_in_genea_doc(genea_path, henry_number, *position, *dry_run)?;
+        }
         Args::Print { genea_path } => {
             let genea = Genea::from_genea_doc(genea_path)?;
             let outline = Outline::from_genea(&genea);
             outline.print(&genea, &mut std::io::stdout().lock())?;
         }
+        Args::Diff {
+            format,
+            old_genea_path,
+            genea_path,
+        } => {
+            let old = Genea::from_genea_doc(old_genea_path)?;
+            let genea = Genea::from_genea_doc(genea_path)?;
+            let changes = diff::diff(&old, &genea);
+            match format {
+                OutputFormat::Human => {
+                    for change in &changes {
+                        println!("{change}");
+                    }
+                }
+                OutputFormat::Json => println!("{}", serde_json::to_string(&changes)?),
+            }
+        }
+        Args::Write {
+            genea_path,
+            output_path,
+        } => {
+            write_genea_doc(genea_path, output_path.as_deref())?;
+        }
         Args::Json {
             genea_path,
             output_path,
@@ -74,6 +310,24 @@ pub fn main() -> anyhow::Result<()> {
             let genea = Genea::from_genea_doc(genea_path)?;
             html::generate(&genea, output_path)?;
         }
+        Args::Redirects {
+            previous_genea_path,
+            genea_path,
+            output_path,
+            html,
+        } => {
+            let previous = Genea::from_genea_doc(previous_genea_path)?;
+            let genea = Genea::from_genea_doc(genea_path)?;
+            let redirects = redirects::generate(&previous, &genea, output_path)?;
+            if let Some(html_path) = html {
+                html::generate_redirects(&redirects, html_path)?;
+            }
+            eprintln!(
+                "{} redirects written to `{}`",
+                redirects.len(),
+                output_path.display()
+            );
+        }
         Args::Serve { genea_path } => {
             let genea = Genea::from_genea_doc(genea_path)?;
             json::generate(&genea, "public/api/v1")?;
@@ -0,0 +1,53 @@
+//! Redirects from the ids people had in an earlier version of `genea.doc` to their
+//! current ids, so that links shared before a renumbering keep working.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use crate::genea::{match_people, Genea};
+
+#[cfg(test)]
+mod tests;
+
+/// Map from an old id to the current id of the same person.
+pub type Redirects = BTreeMap<String, String>;
+
+/// Compares `previous` with `genea` and adds the ids that changed to the table of
+/// redirects at `output_path` (creating it if needed). Entries already in the table are
+/// kept, and updated if their target has moved again, so that links from any earlier
+/// version keep working.
+pub fn generate(previous: &Genea, genea: &Genea, output_path: &Path) -> anyhow::Result<Redirects> {
+    let mut redirects: Redirects = if output_path.exists() {
+        serde_json::from_str(&std::fs::read_to_string(output_path)?)?
+    } else {
+        Redirects::new()
+    };
+
+    let moved: Redirects = match_people(previous, genea)
+        .into_iter()
+        .map(|(old, new)| (previous.id(old), genea.id(new)))
+        .filter(|(old_id, new_id)| old_id != new_id)
+        .collect();
+
+    for target in redirects.values_mut() {
+        if let Some(new_id) = moved.get(target) {
+            *target = new_id.clone();
+        }
+    }
+    redirects.extend(moved);
+
+    // An old id that now belongs to somebody else must lead to them, and
+    // redirects to people who have since been removed lead nowhere.
+    let current_ids: BTreeSet<String> = genea.people().map(|p| genea.id(p)).collect();
+    redirects
+        .retain(|old_id, new_id| !current_ids.contains(old_id) && current_ids.contains(new_id));
+
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(output_path, serde_json::to_string_pretty(&redirects)?)?;
+
+    Ok(redirects)
+}
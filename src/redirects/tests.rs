@@ -0,0 +1,51 @@
+//! The redirect table kept across several versions of a tree.
+
+use std::path::PathBuf;
+
+use super::generate;
+use crate::genea::{Genea, ParseOptions};
+
+fn parse(text: &str) -> Genea {
+    Genea::from_genea_text("genea.doc", text, ParseOptions::default()).unwrap()
+}
+
+/// A path for a redirect table that does not exist yet.
+fn table_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("family-tree-redirects-{}", std::process::id()));
+    let path = dir.join(name).with_extension("json");
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn old_links_follow_people_through_several_renumberings() {
+    let path = table_path("renumberings");
+    let v1 = parse(" 1 0 M 1 0 0 Skon\n 1 1 M 0 0 0 Sarek\n");
+    let v2 = parse(" 1 0 M 0 0 0 Surak\n 2 0 M 1 0 0 Skon\n 2 1 M 0 0 0 Sarek\n");
+    let v3 =
+        parse(" 1 0 M 0 0 0 Surak\n 2 0 M 0 0 0 Solkar\n 3 0 M 1 0 0 Skon\n 3 1 M 0 0 0 Sarek\n");
+
+    // Skon's old id now belongs to Surak, so only Sarek's can be redirected.
+    let redirects = generate(&v1, &v2, &path).unwrap();
+    assert_eq!(redirects.len(), 1);
+    assert_eq!(redirects["1-1"], "2-1");
+
+    // Links to either of Sarek's earlier ids lead to his current one.
+    let redirects = generate(&v2, &v3, &path).unwrap();
+    assert_eq!(redirects.len(), 2);
+    assert_eq!(redirects["1-1"], "3-1");
+    assert_eq!(redirects["2-1"], "3-1");
+
+    let written: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(written["1-1"], "3-1");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn unchanged_tree_needs_no_redirects() {
+    let path = table_path("unchanged");
+    let genea = parse(" 1 0 M 1 0 0 Skon\n 1 1 M 0 0 0 Sarek\n");
+    assert!(generate(&genea, &genea, &path).unwrap().is_empty());
+    let _ = std::fs::remove_file(&path);
+}